@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config::Config;
+use crate::schema::{self, SCHEMA_VERSION};
+
+/// Contents of the manifest file
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AssetManifestFile {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    schema_version: u32,
+    /// Logical asset URL to content-hashed URL
+    assets: BTreeMap<String, String>,
+}
+
+/// Logical asset URLs mapped to their content-hashed URLs, filled while posts are built in parallel
+#[derive(Default)]
+pub struct AssetManifest {
+    entries: Mutex<BTreeMap<String, String>>,
+}
+
+impl AssetManifest {
+    /// Start from the previous build's manifest so posts skipped by incremental builds keep their entries.
+    /// Manifests of another schema version are ignored; every post is rebuilt then anyway.
+    pub fn load(config: &Config) -> Self {
+        let entries = if config.assets.fingerprint {
+            fs::read_to_string(config.public_dir.join(&config.assets.manifest))
+                .ok()
+                .and_then(|content| serde_json::from_str::<AssetManifestFile>(&content).ok())
+                .filter(|file| file.schema_version == SCHEMA_VERSION)
+                .map(|file| file.assets)
+                .unwrap_or_default()
+        } else {
+            BTreeMap::new()
+        };
+        AssetManifest {
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Where the file behind a site URL is: its own path while it exists, otherwise the
+    /// hashed file an earlier build moved it to
+    pub fn path(&self, config: &Config, url: &str) -> PathBuf {
+        let path = config.public_dir.join(url.trim_start_matches('/'));
+        if path.exists() || !config.assets.fingerprint {
+            return path;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .get(url)
+            .map(|hashed| config.public_dir.join(hashed.trim_start_matches('/')))
+            .filter(|hashed| hashed.exists())
+            .unwrap_or(path)
+    }
+
+    /// Move the file behind a site URL below `public_dir` to its content-hashed name and
+    /// return the hashed URL, so only the hashed file is published. A file moved by an earlier
+    /// build keeps its URL. Returns the URL unchanged when fingerprinting is off.
+    pub fn fingerprint(&self, config: &Config, url: &str) -> Result<String> {
+        if !config.assets.fingerprint {
+            return Ok(url.to_string());
+        }
+
+        let source = config.public_dir.join(url.trim_start_matches('/'));
+        if !source.exists() {
+            let moved = self.entries.lock().unwrap().get(url).cloned();
+            if let Some(hashed) = moved.filter(|hashed| config.public_dir.join(hashed.trim_start_matches('/')).exists()) {
+                return Ok(hashed);
+            }
+        }
+        let content = fs::read(&source)
+            .with_context(|| format!("Failed to read asset for hashing: {}", source.display()))?;
+        let hashed_url = hashed_url(url, &content, config.assets.hash_length);
+
+        let target = config.public_dir.join(hashed_url.trim_start_matches('/'));
+        fs::rename(&source, &target)
+            .with_context(|| format!("Failed to move asset to its hashed name: {}", target.display()))?;
+        self.record(config, url, &hashed_url)?;
+        Ok(hashed_url)
+    }
+
+    /// Hashed URL for a file whose URL is handed out before it is written, named after the
+    /// `inputs` that decide its content instead of the content itself
+    pub fn fingerprint_inputs(&self, config: &Config, url: &str, inputs: &[u8]) -> Result<String> {
+        if !config.assets.fingerprint {
+            return Ok(url.to_string());
+        }
+        let hashed_url = hashed_url(url, inputs, config.assets.hash_length);
+        self.record(config, url, &hashed_url)?;
+        Ok(hashed_url)
+    }
+
+    /// Record the hashed URL of a logical URL, deleting the file it was published under before
+    pub fn record(&self, config: &Config, logical: &str, hashed: &str) -> Result<()> {
+        let previous = self
+            .entries
+            .lock()
+            .unwrap()
+            .insert(logical.to_string(), hashed.to_string());
+        if let Some(previous) = previous.filter(|previous| previous != hashed) {
+            let path = config.public_dir.join(previous.trim_start_matches('/'));
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove superseded asset: {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the manifest, dropping entries whose hashed file is gone. Returns the number of entries.
+    pub fn write(&self, config: &Config) -> Result<Option<usize>> {
+        if !config.assets.fingerprint {
+            return Ok(None);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, hashed| config.public_dir.join(hashed.trim_start_matches('/')).exists());
+
+        let path = config.public_dir.join(&config.assets.manifest);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create manifest directory: {}", parent.display()))?;
+        }
+        let file = AssetManifestFile {
+            schema_version: SCHEMA_VERSION,
+            assets: entries.clone(),
+        };
+        schema::validate(&file, &path)?;
+        fs::write(&path, serde_json::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write asset manifest: {}", path.display()))?;
+        Ok(Some(file.assets.len()))
+    }
+}
+
+/// `url` with `hashed_name` as its file name
+fn hashed_url(url: &str, content: &[u8], length: usize) -> String {
+    let (dir, name) = url.rsplit_once('/').unwrap_or(("", url));
+    format!("{dir}/{}", hashed_name(name, content, length))
+}
+
+/// File name with the first `length` hex digits of the content's SHA-256 in front of
+/// the extension, e.g. `cover.jpg` becomes `cover.1a2b3c4d.jpg`
+pub fn hashed_name(name: &str, content: &[u8], length: usize) -> String {
+    let hash = format!("{:x}", Sha256::digest(content));
+    let hash = &hash[..length.clamp(1, hash.len())];
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem}.{hash}.{ext}"),
+        _ => format!("{name}.{hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashed_name_changes_with_content() {
+        let first = hashed_name("intro-photo-cover.jpg", b"first", 8);
+        assert!(first.starts_with("intro-photo-cover."));
+        assert!(first.ends_with(".jpg"));
+        assert_eq!(first.len(), "intro-photo-cover.jpg".len() + 9);
+        assert_eq!(first, hashed_name("intro-photo-cover.jpg", b"first", 8));
+        assert_ne!(first, hashed_name("intro-photo-cover.jpg", b"second", 8));
+        assert_eq!(hashed_name("LICENSE", b"x", 4).len(), "LICENSE".len() + 5);
+    }
+}
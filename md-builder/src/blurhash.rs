@@ -0,0 +1,108 @@
+use image::RgbImage;
+use std::f64::consts::PI;
+
+const BASE83: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// BlurHash of an image with `components_x` × `components_y` cosine components, each from 1 to 9.
+/// See https://github.com/woltapp/blurhash/blob/master/Algorithm.md
+pub fn encode(img: &RgbImage, components_x: u32, components_y: u32) -> String {
+    let (components_x, components_y) = (components_x.clamp(1, 9), components_y.clamp(1, 9));
+    let (width, height) = img.dimensions();
+    let pixels: Vec<[f64; 3]> = img
+        .pixels()
+        .map(|pixel| pixel.0.map(srgb_to_linear))
+        .collect();
+
+    let mut factors = Vec::with_capacity((components_x * components_y) as usize);
+    for j in 0..components_y {
+        for i in 0..components_x {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut factor = [0.0; 3];
+            for y in 0..height {
+                let basis_y = (PI * f64::from(j) * f64::from(y) / f64::from(height)).cos();
+                for x in 0..width {
+                    let basis = basis_y * (PI * f64::from(i) * f64::from(x) / f64::from(width)).cos();
+                    let pixel = pixels[(y * width + x) as usize];
+                    for channel in 0..3 {
+                        factor[channel] += basis * pixel[channel];
+                    }
+                }
+            }
+            let scale = normalisation / f64::from(width * height);
+            factors.push(factor.map(|value| value * scale));
+        }
+    }
+
+    let (dc, ac) = factors.split_first().expect("at least one component");
+    let mut hash = String::new();
+    push_base83(&mut hash, (components_x - 1) + (components_y - 1) * 9, 1);
+
+    let max_value = if ac.is_empty() {
+        push_base83(&mut hash, 0, 1);
+        1.0
+    } else {
+        let actual_max = ac.iter().flatten().fold(0.0_f64, |max, value| max.max(value.abs()));
+        let quantised_max = (actual_max * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+        push_base83(&mut hash, quantised_max, 1);
+        f64::from(quantised_max + 1) / 166.0
+    };
+
+    let [r, g, b] = dc.map(linear_to_srgb);
+    push_base83(&mut hash, (r << 16) + (g << 8) + b, 4);
+    for factor in ac {
+        let [r, g, b] = factor.map(|value| {
+            let scaled = (value / max_value).signum() * (value / max_value).abs().sqrt();
+            (scaled * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u32
+        });
+        push_base83(&mut hash, r * 19 * 19 + g * 19 + b, 2);
+    }
+    hash
+}
+
+fn push_base83(hash: &mut String, value: u32, length: u32) {
+    for digit in (0..length).rev() {
+        let index = (value / 83u32.pow(digit)) % 83;
+        hash.push(BASE83[index as usize] as char);
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let value = f64::from(value) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u32 {
+    let value = value.clamp(0.0, 1.0);
+    let srgb = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0 + 0.5) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_flat_and_striped_images() {
+        // Size flag, maximum AC value, the average color and two characters per AC component
+        let flat = RgbImage::from_pixel(8, 8, image::Rgb([255, 0, 0]));
+        let hash = encode(&flat, 4, 3);
+        assert_eq!(hash.len(), 1 + 1 + 4 + 2 * 11);
+        assert_eq!(&hash[..1], "L");
+        assert_eq!(&hash[2..6], "TI:j");
+        assert_eq!(encode(&flat, 1, 1), "00TI:j");
+
+        // Dark left, light right: the first horizontal component carries the contrast
+        let striped = RgbImage::from_fn(8, 8, |x, _| image::Rgb(if x < 4 { [0; 3] } else { [255; 3] }));
+        let hash = encode(&striped, 2, 1);
+        assert_eq!(hash.len(), 1 + 1 + 4 + 2);
+        assert_ne!(&hash[6..], "fQ");
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::{Context, Result};
+use pulldown_cmark::{Event, Parser, Tag};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::assets::{hashed_name, AssetManifest};
+use crate::config::Config;
+use crate::sections::path_to_slug;
+use crate::{post_path, should_process_file};
+
+/// File name that turns a directory into a page bundle
+pub const BUNDLE_INDEX_FILE: &str = "index.md";
+
+/// A post directory (`content/my-post/index.md`) together with its co-located
+/// images and data files. Resources are only published when the post references them.
+pub struct PageBundle {
+    dir: PathBuf,
+    resources: BTreeSet<String>,
+    /// Referenced resources and the file names they are published under
+    referenced: RefCell<BTreeMap<String, String>>,
+    /// Hex digits of the content hash added to published file names
+    hash_length: Option<usize>,
+}
+
+impl PageBundle {
+    /// Index every file in the bundle directory except the bundle's own `index.md`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut resources = BTreeSet::new();
+
+        for entry in WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            let relative = entry.path().strip_prefix(dir).with_context(|| {
+                format!("Bundle resource outside bundle: {}", entry.path().display())
+            })?;
+            let name = path_to_slug(relative);
+            if name != BUNDLE_INDEX_FILE {
+                resources.insert(name);
+            }
+        }
+
+        Ok(PageBundle {
+            dir: dir.to_path_buf(),
+            resources,
+            referenced: RefCell::new(BTreeMap::new()),
+            hash_length: None,
+        })
+    }
+
+    /// Publish resources under content-hashed file names, e.g. `chart.1a2b3c4d.png`
+    pub fn with_fingerprints(mut self, hash_length: usize) -> Self {
+        self.hash_length = Some(hash_length);
+        self
+    }
+
+    /// Paths of all files belonging to the bundle, including `index.md`
+    pub fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        std::iter::once(self.dir.join(BUNDLE_INDEX_FILE))
+            .chain(self.resources.iter().map(|name| self.dir.join(name)))
+    }
+
+    /// Look up a resource by the relative name used in markdown, e.g. `./images/chart.png`
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let name = normalize_resource_name(name)?;
+        self.resources.contains(&name).then(|| self.dir.join(name))
+    }
+
+    /// Public URL for a referenced resource, marking it to be copied on publish
+    pub fn resource_url(&self, collection: &str, slug: &str, name: &str) -> Option<String> {
+        let name = normalize_resource_name(name)?;
+        if !self.resources.contains(&name) {
+            return None;
+        }
+        let published = match self.hash_length {
+            Some(length) => match fs::read(self.dir.join(&name)) {
+                Ok(content) => hashed_name(&name, &content, length),
+                Err(_) => name.clone(),
+            },
+            None => name.clone(),
+        };
+        let url = format!("/{collection}/{slug}/{published}");
+        self.referenced.borrow_mut().insert(name, published);
+        Some(url)
+    }
+
+    /// Copy the referenced resources to `public/<collection>/<slug>/` unless the published copy is
+    /// newer, and move them to their hashed names when fingerprinting is on. Unreferenced files stay behind.
+    pub fn publish(&self, collection: &str, slug: &str, config: &Config, assets: &AssetManifest) -> Result<usize> {
+        let url_dir = post_path(collection, slug);
+        let target_dir = config.public_dir.join(url_dir.trim_start_matches('/'));
+        let referenced = self.referenced.borrow();
+
+        for (name, published) in referenced.iter() {
+            let source = self.dir.join(name);
+            let url = format!("{url_dir}/{name}");
+            // Unchanged resources keep their published copy
+            if !should_process_file(&source, &target_dir.join(published), false)
+                .with_context(|| format!("Failed to check bundle asset: {}", source.display()))?
+            {
+                if name != published {
+                    assets.record(config, &url, &format!("{url_dir}/{published}"))?;
+                }
+                continue;
+            }
+            let target = target_dir.join(name);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create bundle asset directory: {}", parent.display()))?;
+            }
+            fs::copy(&source, &target).with_context(|| {
+                format!("Failed to copy bundle asset {} to {}", source.display(), target.display())
+            })?;
+            // Hashed like `resource_url` named it, since the copy has the same content
+            assets.fingerprint(config, &url)?;
+        }
+
+        let record = record_path(config, collection, slug);
+        if let Some(parent) = record.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+        }
+        fs::write(&record, serde_json::to_string(&*referenced)?)
+            .with_context(|| format!("Failed to write bundle resources: {}", record.display()))?;
+        Ok(referenced.len())
+    }
+
+    /// Publish the resources the post referenced when it was last processed, so published
+    /// copies that were deleted come back while the post itself is up to date
+    pub fn republish(&self, collection: &str, slug: &str, config: &Config, assets: &AssetManifest) -> Result<usize> {
+        let recorded: BTreeMap<String, String> = fs::read_to_string(record_path(config, collection, slug))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        *self.referenced.borrow_mut() = recorded
+            .into_iter()
+            .filter(|(name, _)| self.resources.contains(name))
+            .collect();
+        self.publish(collection, slug, config, assets)
+    }
+}
+
+/// Resources a bundle post referenced in its last build and their published names,
+/// in `<cache>/bundles/<collection>/<slug>.json`
+fn record_path(config: &Config, collection: &str, slug: &str) -> PathBuf {
+    config.cache.directory.join("bundles").join(collection).join(format!("{slug}.json"))
+}
+
+/// Bundle directories below `content_dir`: every directory with an `index.md`, except those
+/// inside another bundle, whose files all belong to the outer bundle
+pub fn find_bundle_dirs(content_dir: &Path) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = WalkDir::new(content_dir)
+        .min_depth(2)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == BUNDLE_INDEX_FILE)
+        .filter_map(|e| e.path().parent().map(Path::to_path_buf))
+        .collect();
+    // Outer bundles first, so nested ones can be recognised
+    candidates.sort_by_key(|dir| dir.components().count());
+
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in candidates {
+        if !dirs.iter().any(|outer| dir.starts_with(outer)) {
+            dirs.push(dir);
+        }
+    }
+    dirs.sort();
+    dirs
+}
+
+/// Expand `{{< include "file" >}}` directives with the contents of files in a page bundle.
+/// Only bundles expand them, so included files are bundle files whose changes rebuild the post.
+/// Markdown files are inlined as-is, anything else becomes a fenced code block
+/// using the file extension as language. Directives inside code are left as they are.
+pub fn expand_includes(content: &str, base_dir: &Path) -> Result<String> {
+    let include_regex = regex_lite::Regex::new(r#"\{\{<\s*include\s+"([^"]+)"\s*>\}\}"#).unwrap();
+    let code = code_ranges(content);
+    let mut expanded = String::with_capacity(content.len());
+    let mut last_end = 0;
+
+    for caps in include_regex.captures_iter(content) {
+        let directive = caps.get(0).unwrap();
+        if code.iter().any(|range| range.contains(&directive.start())) {
+            continue;
+        }
+        let name = &caps[1];
+        let path = normalize_resource_name(name)
+            .map(|name| base_dir.join(name))
+            .with_context(|| format!("Invalid include path: {name}"))?;
+        let included = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read included file: {}", path.display()))?;
+
+        expanded.push_str(&content[last_end..directive.start()]);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("md") => expanded.push_str(&included),
+            ext => {
+                let fence = if included.contains("```") { "````" } else { "```" };
+                expanded.push_str(&format!(
+                    "{fence}{}\n{}\n{fence}",
+                    ext.unwrap_or_default(),
+                    included.trim_end_matches('\n')
+                ));
+            }
+        }
+        last_end = directive.end();
+    }
+
+    expanded.push_str(&content[last_end..]);
+    Ok(expanded)
+}
+
+/// Byte ranges of code blocks and inline code in markdown
+fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    Parser::new(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Strip a leading `./` and reject URLs and paths that escape the post directory
+fn normalize_resource_name(name: &str) -> Option<String> {
+    if name.contains("://") || name.starts_with('/') || name.starts_with('#') {
+        return None;
+    }
+    let name = name.split(['?', '#']).next().unwrap_or(name);
+    let normalized = path_to_slug(Path::new(name));
+    if normalized.is_empty() || name.split('/').any(|part| part == "..") {
+        None
+    } else {
+        Some(normalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_bundles_publish_only_referenced_resources() {
+        let dir = std::env::temp_dir().join(format!("site-builder-bundles-{}", std::process::id()));
+        let content = dir.join("content");
+        write(&content.join("loose.md"), "# Not a bundle");
+        write(&content.join("trip/index.md"), "# Trip");
+        write(&content.join("trip/chart.png"), "png");
+        write(&content.join("trip/data/unused.csv"), "a,b");
+        write(&content.join("trip/day-two/index.md"), "# Part of the trip bundle");
+        write(&content.join("guides/setup/index.md"), "# Setup");
+
+        assert_eq!(
+            find_bundle_dirs(&content),
+            [content.join("guides/setup"), content.join("trip")]
+        );
+
+        let bundle = PageBundle::load(&content.join("trip")).unwrap();
+        assert_eq!(bundle.resolve("./chart.png"), Some(content.join("trip/chart.png")));
+        assert_eq!(bundle.resolve("day-two/index.md"), Some(content.join("trip/day-two/index.md")));
+        assert_eq!(bundle.resolve("../loose.md"), None);
+        assert_eq!(bundle.resource_url("blog", "trip", "missing.png"), None);
+        assert_eq!(
+            bundle.resource_url("blog", "trip", "./chart.png?v=2").as_deref(),
+            Some("/blog/trip/chart.png")
+        );
+
+        let mut config = Config {
+            public_dir: dir.join("public"),
+            ..Default::default()
+        };
+        config.cache.directory = dir.join("cache");
+        let public = &config.public_dir;
+        assert_eq!(bundle.publish("blog", "trip", &config, &AssetManifest::default()).unwrap(), 1);
+        assert!(public.join("blog/trip/chart.png").exists());
+        assert!(!public.join("blog/trip/data/unused.csv").exists());
+
+        // A post that is not processed again still gets its published copies back
+        fs::remove_file(public.join("blog/trip/chart.png")).unwrap();
+        let skipped = PageBundle::load(&content.join("trip")).unwrap();
+        assert_eq!(skipped.republish("blog", "trip", &config, &AssetManifest::default()).unwrap(), 1);
+        assert!(public.join("blog/trip/chart.png").exists());
+
+        // Fingerprinted resources are only published under their hashed name, and a changed
+        // resource replaces the hashed file of the last build
+        config.assets.fingerprint = true;
+        let public = &config.public_dir;
+        fs::remove_dir_all(public).unwrap();
+        let assets = AssetManifest::default();
+        let bundle = PageBundle::load(&content.join("trip")).unwrap().with_fingerprints(8);
+        let first = bundle.resource_url("blog", "trip", "chart.png").unwrap();
+        bundle.publish("blog", "trip", &config, &assets).unwrap();
+        assert!(public.join(first.trim_start_matches('/')).exists());
+        assert!(!public.join("blog/trip/chart.png").exists());
+
+        write(&content.join("trip/chart.png"), "new png");
+        let bundle = PageBundle::load(&content.join("trip")).unwrap().with_fingerprints(8);
+        let second = bundle.resource_url("blog", "trip", "chart.png").unwrap();
+        bundle.publish("blog", "trip", &config, &assets).unwrap();
+        assert_ne!(first, second);
+        assert!(public.join(second.trim_start_matches('/')).exists());
+        assert!(!public.join(first.trim_start_matches('/')).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_includes_inside_code_are_left_alone() {
+        let dir = std::env::temp_dir().join(format!("site-builder-includes-{}", std::process::id()));
+        write(&dir.join("snippet.rs"), "fn main() {}\n");
+        write(&dir.join("note.md"), "A *note*.");
+
+        let content = "{{< include \"note.md\" >}}\n\nUse `{{< include \"missing.rs\" >}}` like this:\n\n```markdown\n{{< include \"missing.md\" >}}\n```\n\n{{< include \"snippet.rs\" >}}\n";
+        let expanded = expand_includes(content, &dir).unwrap();
+        assert_eq!(
+            expanded,
+            "A *note*.\n\nUse `{{< include \"missing.rs\" >}}` like this:\n\n```markdown\n{{< include \"missing.md\" >}}\n```\n\n```rs\nfn main() {}\n```\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
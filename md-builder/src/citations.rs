@@ -1,7 +1,7 @@
 use anyhow::{Context, Result};
 use hayagriva::{
     citationberg::{IndependentStyle, Locale, LocaleFile},
-    BibliographyDriver, BibliographyRequest, CitationItem, CitationRequest, ElemChild, Library,
+    BibliographyDriver, BibliographyRequest, CitationItem, CitationRequest, ElemChild, Entry, Library,
 };
 use pulldown_cmark::{Event, Options, Parser};
 use std::{
@@ -26,6 +26,12 @@ pub struct CitationResult {
     pub html_content: String,
     pub references_html: String,
     pub has_citations: bool,
+    /// Formatted in-text citation per cited key, as plain text
+    pub citations: HashMap<String, String>,
+    /// Formatted reference list entries, as plain text
+    pub references: Vec<String>,
+    /// Bibliography entries cited in the post, in order of first citation
+    pub entries: Vec<Entry>,
 }
 
 impl CitationConfig {
@@ -96,6 +102,24 @@ impl Default for CitationConfig {
     }
 }
 
+/// Format a single entry as a reference list item in the configured style, as plain text
+pub fn format_reference(entry: &Entry, config: &CitationConfig) -> Option<String> {
+    let mut driver = BibliographyDriver::new();
+    driver.citation(CitationRequest::from_items(
+        vec![CitationItem::with_entry(entry)],
+        &config.style,
+        &config.locales,
+    ));
+    let result = driver.finish(BibliographyRequest {
+        style: &config.style,
+        locale: None,
+        locale_files: &config.locales,
+    });
+    result
+        .bibliography
+        .and_then(|bibliography| bibliography.items.first().map(|item| item.content.to_string()))
+}
+
 pub fn parse_markdown_with_citations(
     content: &str,
     config: &CitationConfig,
@@ -116,13 +140,16 @@ pub fn parse_markdown_with_citations(
 
     // Track used citations to build reference list
     let mut cited_keys = HashSet::new();
+    let mut entries = Vec::new();
 
     // First pass: collect all citations and register them with the drivers
     let content_with_placeholders =
         citation_regex.replace_all(content, |caps: &regex_lite::Captures| {
             let key = &caps[1];
             if let Some(entry) = config.bibliography.get(key) {
-                cited_keys.insert(key.to_string());
+                if cited_keys.insert(key.to_string()) {
+                    entries.push(entry.clone());
+                }
                 let items = vec![CitationItem::with_entry(entry)];
 
                 // Register with both drivers
@@ -159,6 +186,7 @@ pub fn parse_markdown_with_citations(
 
     // Create a map of citation keys to their formatted in-text citations
     let mut citations = HashMap::new();
+    let mut citation_texts = HashMap::new();
     for cite in in_text_result.citations {
         let key = cite
             .citation
@@ -174,6 +202,7 @@ pub fn parse_markdown_with_citations(
             .collect::<Option<String>>();
 
         if let Some(key) = key {
+            citation_texts.insert(key.clone(), cite.citation.to_string());
             citations.insert(
                 format!("CITATION_PLACEHOLDER_{key}"),
                 format!(
@@ -232,6 +261,7 @@ pub fn parse_markdown_with_citations(
 
     // Generate references section if there are citations
     let mut references_html = String::new();
+    let mut references = Vec::new();
     if !cited_keys.is_empty() {
         references_html.push_str("<div class=\"references\">\n");
         references_html.push_str("<h2>References</h2>\n");
@@ -242,6 +272,7 @@ pub fn parse_markdown_with_citations(
                 "<div id=\"ref-{}\" class=\"reference-item\">{}</div>\n",
                 entry.key, entry.content
             ));
+            references.push(entry.content.to_string());
         }
 
         references_html.push_str("</div>\n");
@@ -252,5 +283,8 @@ pub fn parse_markdown_with_citations(
         html_content: html_output,
         references_html,
         has_citations: !cited_keys.is_empty(),
+        citations: citation_texts,
+        references,
+        entries,
     })
 }
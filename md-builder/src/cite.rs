@@ -0,0 +1,526 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Utc};
+use hayagriva::types::{Date, EntryType, FormatString, Person, QualifiedUrl};
+use hayagriva::Entry;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+use crate::citations::{format_reference, CitationConfig};
+use crate::config::Config;
+use crate::{json_ld, post_path};
+
+/// Everything a reader needs to cite a post
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct PostCitation {
+    /// The post as a reference in the configured citation style, as plain text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub formatted: Option<String>,
+    pub bibtex: String,
+    pub ris: String,
+    pub csl_json: serde_json::Value,
+    /// Site paths of the same records as downloadable files
+    pub files: CitationFiles,
+    /// Downloads of the works the post cites, when it cites any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub references: Option<ReferenceFiles>,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct CitationFiles {
+    pub bibtex: String,
+    pub ris: String,
+    pub csl_json: String,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct ReferenceFiles {
+    pub bibtex: String,
+    pub ris: String,
+    pub count: usize,
+}
+
+/// The post fields that end up in its citation record
+pub struct CiteInput<'a> {
+    pub collection: &'a str,
+    pub slug: &'a str,
+    pub title: &'a str,
+    pub date: DateTime<Utc>,
+    pub language: Option<&'a str>,
+}
+
+/// Directory of a post's records, `None` when its collection gets none
+pub fn record_dir(config: &Config, collection: &str, slug: &str) -> Option<PathBuf> {
+    let settings = &config.cite;
+    (settings.enabled && settings.collections.iter().any(|c| c == collection)).then(|| {
+        config
+            .public_dir
+            .join(post_path(collection, slug).trim_start_matches('/'))
+    })
+}
+
+/// Build the records describing a post and write them as `cite.bib`, `cite.ris` and `cite.json`
+/// below `<public_dir>/<collection>/<slug>/`, next to `references.bib` and `references.ris` with
+/// the works it cites. Returns `None` when the collection is not configured for records.
+pub fn write_post_citation(
+    config: &Config,
+    input: &CiteInput,
+    citation_config: Option<&CitationConfig>,
+    cited: &[Entry],
+) -> Result<Option<PostCitation>> {
+    let Some(dir) = record_dir(config, input.collection, input.slug) else {
+        return Ok(None);
+    };
+
+    let entry = post_entry(config, input);
+    let record = Record::from_entry(&entry);
+    let bibtex = record.to_bibtex();
+    let ris = record.to_ris();
+    let csl_json = record.to_csl_json();
+
+    let path = post_path(input.collection, input.slug);
+    write_file(&dir.join("cite.bib"), &bibtex)?;
+    write_file(&dir.join("cite.ris"), &ris)?;
+    write_file(&dir.join("cite.json"), &serde_json::to_string_pretty(&[&csl_json])?)?;
+
+    let references = if cited.is_empty() {
+        remove_file(&dir.join("references.bib"))?;
+        remove_file(&dir.join("references.ris"))?;
+        None
+    } else {
+        let records: Vec<Record> = cited.iter().map(Record::from_entry).collect();
+        let bibtex: Vec<String> = records.iter().map(Record::to_bibtex).collect();
+        let ris: Vec<String> = records.iter().map(Record::to_ris).collect();
+        write_file(&dir.join("references.bib"), &bibtex.join("\n"))?;
+        write_file(&dir.join("references.ris"), &ris.join("\n"))?;
+        Some(ReferenceFiles {
+            bibtex: format!("{path}/references.bib"),
+            ris: format!("{path}/references.ris"),
+            count: records.len(),
+        })
+    };
+
+    Ok(Some(PostCitation {
+        formatted: citation_config.and_then(|c| format_reference(&entry, c)),
+        bibtex,
+        ris,
+        csl_json,
+        files: CitationFiles {
+            bibtex: format!("{path}/cite.bib"),
+            ris: format!("{path}/cite.ris"),
+            csl_json: format!("{path}/cite.json"),
+        },
+        references,
+    }))
+}
+
+/// The post as a bibliography entry: an article whose parent is the site's blog
+fn post_entry(config: &Config, input: &CiteInput) -> Entry {
+    let mut entry = Entry::new(&format!("{}-{}", input.collection, input.slug), EntryType::Article);
+    entry.set_title(FormatString::from(input.title.to_string()));
+    if let Some(author) = config.site.author.as_deref().and_then(person) {
+        entry.set_authors(vec![author]);
+    }
+    entry.set_date(Date {
+        year: input.date.year(),
+        month: Some(input.date.month0() as u8),
+        day: Some(input.date.day0() as u8),
+        approximate: false,
+    });
+    let url = post_path(input.collection, input.slug);
+    if let Some(url) = config.absolute_url(&url).and_then(|url| url::Url::parse(&url).ok()) {
+        entry.set_url(QualifiedUrl::new(url, None));
+    }
+    if let Ok(language) = input.language.unwrap_or(&config.site.language).parse() {
+        entry.set_language(language);
+    }
+
+    let mut blog = Entry::new("blog", EntryType::Blog);
+    blog.set_title(FormatString::from(config.site.title.clone()));
+    entry.set_parents(vec![blog]);
+    entry
+}
+
+/// `Family, Given` or `Given Family`
+fn person(name: &str) -> Option<Person> {
+    let parts: Vec<&str> = if name.contains(',') {
+        name.split(',').collect()
+    } else if let Some((given, family)) = name.rsplit_once(' ') {
+        vec![family, given]
+    } else {
+        vec![name]
+    };
+    Person::from_strings(parts).ok()
+}
+
+/// Kinds of works, as far as BibTeX, RIS and CSL tell them apart
+#[derive(Clone, Copy)]
+enum Kind {
+    JournalArticle,
+    BlogPost,
+    ConferencePaper,
+    Book,
+    Chapter,
+    Thesis,
+    Report,
+    WebPage,
+    Misc,
+}
+
+impl Kind {
+    fn of(entry: &Entry) -> Self {
+        let parent = entry.parents().first().map(Entry::entry_type);
+        match (entry.entry_type(), parent) {
+            (EntryType::Article | EntryType::Post, Some(EntryType::Blog)) => Kind::BlogPost,
+            (EntryType::Article, Some(EntryType::Proceedings | EntryType::Conference)) => Kind::ConferencePaper,
+            (EntryType::Article, Some(EntryType::Web)) => Kind::WebPage,
+            (EntryType::Article, _) => Kind::JournalArticle,
+            (EntryType::Book | EntryType::Anthology | EntryType::Proceedings, _) => Kind::Book,
+            (EntryType::Chapter | EntryType::Anthos, _) => Kind::Chapter,
+            (EntryType::Thesis, _) => Kind::Thesis,
+            (EntryType::Report, _) => Kind::Report,
+            (EntryType::Web | EntryType::Blog | EntryType::Post, _) => Kind::WebPage,
+            _ => Kind::Misc,
+        }
+    }
+
+    fn bibtex(self) -> &'static str {
+        match self {
+            Kind::JournalArticle => "article",
+            Kind::BlogPost | Kind::WebPage => "online",
+            Kind::ConferencePaper => "inproceedings",
+            Kind::Book => "book",
+            Kind::Chapter => "incollection",
+            Kind::Thesis => "phdthesis",
+            Kind::Report => "techreport",
+            Kind::Misc => "misc",
+        }
+    }
+
+    fn ris(self) -> &'static str {
+        match self {
+            Kind::JournalArticle => "JOUR",
+            Kind::BlogPost => "BLOG",
+            Kind::ConferencePaper => "CPAPER",
+            Kind::Book => "BOOK",
+            Kind::Chapter => "CHAP",
+            Kind::Thesis => "THES",
+            Kind::Report => "RPRT",
+            Kind::WebPage => "ELEC",
+            Kind::Misc => "GEN",
+        }
+    }
+
+    fn csl(self) -> &'static str {
+        match self {
+            Kind::JournalArticle => "article-journal",
+            Kind::BlogPost => "post-weblog",
+            Kind::ConferencePaper => "paper-conference",
+            Kind::Book => "book",
+            Kind::Chapter => "chapter",
+            Kind::Thesis => "thesis",
+            Kind::Report => "report",
+            Kind::WebPage => "webpage",
+            Kind::Misc => "document",
+        }
+    }
+
+    /// BibTeX field for the title of the containing work
+    fn container_field(self) -> &'static str {
+        match self {
+            Kind::JournalArticle => "journal",
+            Kind::BlogPost | Kind::WebPage => "organization",
+            Kind::ConferencePaper | Kind::Chapter => "booktitle",
+            Kind::Book => "series",
+            Kind::Thesis | Kind::Report | Kind::Misc => "howpublished",
+        }
+    }
+
+    /// BibTeX field for the publisher
+    fn publisher_field(self) -> &'static str {
+        match self {
+            Kind::Thesis => "school",
+            Kind::Report => "institution",
+            _ => "publisher",
+        }
+    }
+}
+
+/// The fields of an entry that the export formats share
+struct Record {
+    key: String,
+    kind: Kind,
+    title: Option<String>,
+    /// Family and given names
+    authors: Vec<(String, Option<String>)>,
+    date: Option<Date>,
+    container: Option<String>,
+    publisher: Option<String>,
+    volume: Option<String>,
+    issue: Option<String>,
+    pages: Option<String>,
+    doi: Option<String>,
+    url: Option<String>,
+    accessed: Option<Date>,
+    language: Option<String>,
+}
+
+impl Record {
+    fn from_entry(entry: &Entry) -> Self {
+        let authors = entry
+            .authors()
+            .unwrap_or_default()
+            .iter()
+            .map(|person| {
+                let family = match &person.prefix {
+                    Some(prefix) => format!("{prefix} {}", person.name),
+                    None => person.name.clone(),
+                };
+                (family, person.given_name.clone())
+            })
+            .collect();
+        let parent = entry.parents().first();
+
+        Record {
+            key: entry.key().to_string(),
+            kind: Kind::of(entry),
+            title: entry.title().map(ToString::to_string),
+            authors,
+            date: entry.date_any().cloned(),
+            container: parent.and_then(|p| p.title()).map(ToString::to_string),
+            publisher: entry
+                .publisher()
+                .or_else(|| parent.and_then(|p| p.publisher()))
+                .and_then(|p| p.name())
+                .map(ToString::to_string),
+            volume: entry.volume().or_else(|| parent.and_then(|p| p.volume())).map(ToString::to_string),
+            issue: entry.issue().or_else(|| parent.and_then(|p| p.issue())).map(ToString::to_string),
+            pages: entry.page_range().map(ToString::to_string),
+            doi: entry.doi().map(str::to_string),
+            url: entry.url_any().map(|url| url.value.to_string()),
+            accessed: entry.url_any().and_then(|url| url.visit_date),
+            language: entry.language().map(ToString::to_string),
+        }
+    }
+
+    fn to_bibtex(&self) -> String {
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        let mut field = |name: &'static str, value: Option<&String>| {
+            if let Some(value) = value {
+                fields.push((name, bibtex_escape(value)));
+            }
+        };
+        field("title", self.title.as_ref());
+        let authors = (!self.authors.is_empty()).then(|| {
+            self.authors
+                .iter()
+                .map(|(family, given)| match given {
+                    Some(given) => format!("{family}, {given}"),
+                    None => family.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(" and ")
+        });
+        field("author", authors.as_ref());
+        field("year", self.date.as_ref().map(|d| d.year.to_string()).as_ref());
+        field("month", self.date.as_ref().and_then(|d| d.month).map(|m| (m + 1).to_string()).as_ref());
+        field(self.kind.container_field(), self.container.as_ref());
+        field(self.kind.publisher_field(), self.publisher.as_ref());
+        field("volume", self.volume.as_ref());
+        field("number", self.issue.as_ref());
+        field("pages", self.pages.as_ref().map(|p| bibtex_page_range(p)).as_ref());
+        field("language", self.language.as_ref());
+        field("urldate", self.accessed.as_ref().map(iso_date).as_ref());
+        // Identifiers are written verbatim
+        if let Some(doi) = &self.doi {
+            fields.push(("doi", doi.clone()));
+        }
+        if let Some(url) = &self.url {
+            fields.push(("url", url.clone()));
+        }
+
+        let mut bibtex = format!("@{}{{{},\n", self.kind.bibtex(), self.key);
+        for (name, value) in fields {
+            bibtex.push_str(&format!("  {name} = {{{value}}},\n"));
+        }
+        bibtex.push_str("}\n");
+        bibtex
+    }
+
+    fn to_ris(&self) -> String {
+        let mut lines = vec![("TY", self.kind.ris().to_string())];
+        let mut line = |tag: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                lines.push((tag, value));
+            }
+        };
+        line("TI", self.title.clone());
+        for (family, given) in &self.authors {
+            line("AU", Some(match given {
+                Some(given) => format!("{family}, {given}"),
+                None => family.clone(),
+            }));
+        }
+        line("PY", self.date.as_ref().map(|d| d.year.to_string()));
+        line("DA", self.date.as_ref().map(ris_date));
+        line("T2", self.container.clone());
+        line("PB", self.publisher.clone());
+        line("VL", self.volume.clone());
+        line("IS", self.issue.clone());
+        if let Some(pages) = &self.pages {
+            let (start, end) = pages.split_once(['-', '–']).unwrap_or((pages, ""));
+            line("SP", Some(start.trim().to_string()));
+            line("EP", (!end.trim_matches('-').is_empty()).then(|| end.trim_matches('-').trim().to_string()));
+        }
+        line("DO", self.doi.clone());
+        line("UR", self.url.clone());
+        line("Y2", self.accessed.as_ref().map(ris_date));
+        line("LA", self.language.clone());
+        line("ID", Some(self.key.clone()));
+
+        let mut ris: String = lines
+            .into_iter()
+            .map(|(tag, value)| format!("{tag}  - {}\n", value.replace('\n', " ")))
+            .collect();
+        ris.push_str("ER  - \n");
+        ris
+    }
+
+    fn to_csl_json(&self) -> Value {
+        let date_parts = |date: &Date| {
+            let mut parts = vec![date.year];
+            if let Some(month) = date.month {
+                parts.push(month as i32 + 1);
+                if let Some(day) = date.day {
+                    parts.push(day as i32 + 1);
+                }
+            }
+            json!({ "date-parts": [parts] })
+        };
+        let mut item = json!({
+            "id": self.key,
+            "type": self.kind.csl(),
+            "title": self.title,
+            "author": self.authors.iter().map(|(family, given)| json!({ "family": family, "given": given })).collect::<Vec<_>>(),
+            "issued": self.date.as_ref().map(date_parts),
+            "container-title": self.container,
+            "publisher": self.publisher,
+            "volume": self.volume,
+            "issue": self.issue,
+            "page": self.pages,
+            "DOI": self.doi,
+            "URL": self.url,
+            "accessed": self.accessed.as_ref().map(date_parts),
+            "language": self.language,
+        });
+        json_ld::prune(&mut item);
+        item
+    }
+}
+
+/// Escape the characters BibTeX treats specially in field values
+fn bibtex_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '&' | '%' | '$' | '#' | '_' | '{' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// BibTeX separates page ranges with `--`
+fn bibtex_page_range(pages: &str) -> String {
+    if pages.contains("--") {
+        pages.to_string()
+    } else {
+        pages.replace(['-', '–'], "--")
+    }
+}
+
+/// `YYYY-MM-DD`, as far as the date is known
+fn iso_date(date: &Date) -> String {
+    date.to_string().trim_start_matches('~').to_string()
+}
+
+/// `YYYY/MM/DD/`, with empty parts for unknown month or day
+fn ris_date(date: &Date) -> String {
+    let part = |value: Option<u8>| value.map(|v| format!("{:02}", v + 1)).unwrap_or_default();
+    format!("{:04}/{}/{}/", date.year, part(date.month), part(date.month.and(date.day)))
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_for_cited_works_and_the_post() {
+        let library = hayagriva::io::from_yaml_str(
+            "knuth:\n  type: article\n  title: Literate Programming & Tools\n  author: Knuth, Donald E.\n  date: 1984-05\n  page-range: 97-111\n  serial-number:\n    doi: 10.1093/comjnl/27.2.97\n  parent:\n    type: periodical\n    title: The Computer Journal\n    volume: 27\n    issue: 2\n",
+        )
+        .unwrap();
+        let knuth = Record::from_entry(library.get("knuth").unwrap());
+
+        let bibtex = knuth.to_bibtex();
+        assert!(bibtex.starts_with("@article{knuth,\n"));
+        assert!(bibtex.contains("  title = {Literate Programming \\& Tools},\n"));
+        assert!(bibtex.contains("  author = {Knuth, Donald E.},\n"));
+        assert!(bibtex.contains("  month = {5},\n"));
+        assert!(bibtex.contains("  journal = {The Computer Journal},\n"));
+        assert!(bibtex.contains("  pages = {97--111},\n"));
+        assert!(bibtex.contains("  doi = {10.1093/comjnl/27.2.97},\n"));
+
+        let ris = knuth.to_ris();
+        assert!(ris.starts_with("TY  - JOUR\n"));
+        assert!(ris.contains("DA  - 1984/05//\n"));
+        assert!(ris.contains("VL  - 27\nIS  - 2\nSP  - 97\nEP  - 111\n"));
+        assert!(ris.ends_with("ER  - \n"));
+
+        let csl = knuth.to_csl_json();
+        assert_eq!(csl["type"], "article-journal");
+        assert_eq!(csl["issued"]["date-parts"], json!([[1984, 5]]));
+        assert_eq!(csl["author"][0]["given"], "Donald E.");
+
+        let mut config = Config::default();
+        config.site.url = Some("https://example.com".to_string());
+        config.site.author = Some("Ada Lovelace".to_string());
+        let input = CiteInput {
+            collection: "blog",
+            slug: "hello",
+            title: "Hello",
+            date: "2024-03-20T12:00:00Z".parse().unwrap(),
+            language: None,
+        };
+        let post = Record::from_entry(&post_entry(&config, &input));
+        let bibtex = post.to_bibtex();
+        assert!(bibtex.starts_with("@online{blog-hello,\n"));
+        assert!(bibtex.contains("  author = {Lovelace, Ada},\n"));
+        assert!(bibtex.contains("  url = {https://example.com/blog/hello},\n"));
+        assert!(post.to_ris().contains("TY  - BLOG\n"));
+        assert_eq!(post.to_csl_json()["issued"]["date-parts"], json!([[2024, 3, 20]]));
+    }
+}
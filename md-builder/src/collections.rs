@@ -0,0 +1,214 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use crate::config::{CollectionSettings, FieldSchema, FieldType, SortOrder};
+use crate::Post;
+
+/// Keys of the post JSON. Schema fields are flattened next to them, so a field
+/// with one of these names would produce a duplicate key.
+const RESERVED_FIELDS: &[&str] = &[
+    "schema_version",
+    "title",
+    "date",
+    "updated",
+    "slug",
+    "section",
+    "language",
+    "translation_key",
+    "content",
+    "description",
+    "tags",
+    "series",
+    "cover",
+    "seo",
+    "json_ld",
+    "cite",
+];
+
+/// Reject schema fields that would clash with the built-in post fields
+pub fn check_schema(collection: &CollectionSettings) -> Result<()> {
+    for field in &collection.fields {
+        if RESERVED_FIELDS.contains(&field.name.as_str()) {
+            bail!(
+                "Field `{}` of collection `{}` is reserved for the built-in post data",
+                field.name,
+                collection.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check the extra frontmatter of a file against the collection schema and convert
+/// it to JSON. Fields that are not declared in the schema are dropped.
+pub fn validate_fields(
+    schema: &[FieldSchema],
+    frontmatter: &BTreeMap<String, serde_yaml::Value>,
+) -> Result<BTreeMap<String, Value>> {
+    let mut fields = BTreeMap::new();
+
+    for field in schema {
+        match frontmatter.get(&field.name) {
+            Some(serde_yaml::Value::Null) | None if field.required => {
+                bail!("Missing required field `{}`", field.name);
+            }
+            Some(serde_yaml::Value::Null) | None => {}
+            Some(value) => {
+                let value = convert_field(field.field_type, value)
+                    .map_err(|e| anyhow!("Invalid field `{}`: {e}", field.name))?;
+                fields.insert(field.name.clone(), value);
+            }
+        }
+    }
+
+    Ok(fields)
+}
+
+fn convert_field(field_type: FieldType, value: &serde_yaml::Value) -> Result<Value> {
+    use serde_yaml::Value as Yaml;
+
+    let converted = match (field_type, value) {
+        (FieldType::String, Yaml::String(s)) => Value::String(s.clone()),
+        (FieldType::String, Yaml::Number(n)) => Value::String(n.to_string()),
+        (FieldType::Number, Yaml::Number(n)) => serde_json::to_value(n)?,
+        (FieldType::Boolean, Yaml::Bool(b)) => Value::Bool(*b),
+        (FieldType::Date, Yaml::String(s)) => Value::String(parse_date(s)?.to_rfc3339()),
+        (FieldType::List, Yaml::String(s)) => s
+            .split(',')
+            .map(|item| Value::String(item.trim().to_string()))
+            .collect(),
+        (FieldType::List, Yaml::Sequence(items)) => items
+            .iter()
+            .map(|item| convert_field(FieldType::String, item))
+            .collect::<Result<_>>()?,
+        (FieldType::Url, Yaml::String(s))
+            if s.starts_with("https://") || s.starts_with("http://") || s.starts_with('/') =>
+        {
+            Value::String(s.clone())
+        }
+        (field_type, value) => bail!("expected {field_type:?}, found {value:?}"),
+    };
+
+    Ok(converted)
+}
+
+fn parse_date(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| anyhow!("`{value}` is not an RFC 3339 or YYYY-MM-DD date"))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+}
+
+/// Keep only the schema fields the collection lists in `index_fields`
+pub fn index_fields(
+    collection: &CollectionSettings,
+    fields: &BTreeMap<String, Value>,
+) -> BTreeMap<String, Value> {
+    fields
+        .iter()
+        .filter(|(name, _)| collection.index_fields.contains(name))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Sort posts by the collection's sort key. Posts without the key go last.
+pub fn sort_posts(collection: &CollectionSettings, posts: &mut [Post]) {
+    posts.sort_by(|a, b| {
+        let ordering = match (sort_value(collection, a), sort_value(collection, b)) {
+            (Some(a), Some(b)) => compare_values(&a, &b),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        match collection.sort_order {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    });
+}
+
+fn sort_value(collection: &CollectionSettings, post: &Post) -> Option<Value> {
+    match collection.sort_by.as_str() {
+        "date" => Some(Value::String(post.date.to_rfc3339())),
+        "title" => Some(Value::String(post.title.clone())),
+        "slug" => Some(Value::String(post.slug.clone())),
+        field => post.fields.get(field).cloned(),
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (a, b) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn field(name: &str, field_type: FieldType, required: bool) -> FieldSchema {
+        FieldSchema {
+            name: name.to_string(),
+            field_type,
+            required,
+        }
+    }
+
+    #[test]
+    fn test_fields_are_checked_and_posts_sorted() {
+        let schema = [
+            field("client", FieldType::String, true),
+            field("year", FieldType::Number, false),
+            field("stack", FieldType::List, false),
+            field("shipped", FieldType::Date, false),
+            field("repo", FieldType::Url, false),
+        ];
+        let frontmatter: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(
+            "client: Fjord Salmon\nyear: 2023\nstack: rust, react\nshipped: 2023-06-01\nrepo: https://example.com\nignored: true",
+        )
+        .unwrap();
+        let fields = validate_fields(&schema, &frontmatter).unwrap();
+        assert_eq!(fields["client"], "Fjord Salmon");
+        assert_eq!(fields["year"], 2023);
+        assert_eq!(fields["stack"], serde_json::json!(["rust", "react"]));
+        assert_eq!(fields["shipped"], "2023-06-01T00:00:00+00:00");
+        assert!(!fields.contains_key("ignored"));
+
+        let missing: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str("year: 2023").unwrap();
+        assert!(validate_fields(&schema, &missing).is_err());
+        let wrong_type: BTreeMap<String, serde_yaml::Value> =
+            serde_yaml::from_str("client: Acme\nrepo: ftp://example.com").unwrap();
+        assert!(validate_fields(&schema, &wrong_type).is_err());
+
+        let mut collection = Config::default().collections().remove(0);
+        collection.sort_by = "year".to_string();
+        collection.index_fields = vec!["year".to_string()];
+        let post = |slug: &str, year: Option<i64>| Post {
+            slug: slug.to_string(),
+            fields: year.map(|year| ("year".to_string(), Value::from(year))).into_iter().collect(),
+            ..Default::default()
+        };
+        let mut posts = vec![post("old", Some(2019)), post("undated", None), post("new", Some(2024))];
+        sort_posts(&collection, &mut posts);
+        let slugs: Vec<&str> = posts.iter().map(|p| p.slug.as_str()).collect();
+        assert_eq!(slugs, ["new", "old", "undated"]);
+        assert_eq!(index_fields(&collection, &fields).keys().collect::<Vec<_>>(), ["year"]);
+
+        collection.fields = vec![field("year", FieldType::Number, false)];
+        assert!(check_schema(&collection).is_ok());
+        collection.fields.push(field("tags", FieldType::List, false));
+        assert!(check_schema(&collection).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::fs;
 
@@ -28,6 +29,59 @@ pub struct Config {
     /// Cache settings
     #[serde(default)]
     pub cache: CacheSettings,
+    
+    /// Content collections. When empty, a single `blog` collection is built
+    /// from `content_dir` and `output_dir`.
+    #[serde(default)]
+    pub collections: Vec<CollectionSettings>,
+    
+    /// Site-wide metadata used for absolute URLs and feeds
+    #[serde(default)]
+    pub site: SiteSettings,
+    
+    /// RSS and Atom feed settings
+    #[serde(default)]
+    pub feeds: FeedSettings,
+    
+    /// sitemap.xml settings
+    #[serde(default)]
+    pub sitemap: SitemapSettings,
+    
+    /// robots.txt settings
+    #[serde(default)]
+    pub robots: RobotsSettings,
+    
+    /// Full-text search index settings
+    #[serde(default)]
+    pub search: SearchSettings,
+    
+    /// Generated Open Graph preview image settings
+    #[serde(default)]
+    pub og_images: OgImageSettings,
+    
+    /// Static HTML prerendering settings
+    #[serde(default)]
+    pub prerender: PrerenderSettings,
+    
+    /// Paginated index chunks for lazy loading
+    #[serde(default)]
+    pub pagination: PaginationSettings,
+    
+    /// Content-hashed asset names for cache busting
+    #[serde(default)]
+    pub assets: AssetSettings,
+    
+    /// Markdown copies of posts plus `llms.txt` and `llms-full.txt`
+    #[serde(default)]
+    pub llms: LlmsSettings,
+    
+    /// "Cite this post" records and downloads of the works each post cites
+    #[serde(default)]
+    pub cite: CiteSettings,
+    
+    /// Output optimization for production builds
+    #[serde(default)]
+    pub output: OutputSettings,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -61,25 +115,457 @@ pub struct ZoteroSettings {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImageSettings {
-    /// Cover image width in pixels
-    #[serde(default = "default_cover_width")]
-    pub cover_width: u32,
-    
-    /// Cover image height in pixels
-    #[serde(default = "default_cover_height")]
-    pub cover_height: u32,
-    
-    /// Thumbnail width in pixels
-    #[serde(default = "default_thumb_width")]
-    pub thumbnail_width: u32,
-    
-    /// Thumbnail height in pixels
-    #[serde(default = "default_thumb_height")]
-    pub thumbnail_height: u32,
+    /// Resized variants of every cover image. Presets named `cover` and `thumbnail`
+    /// fill the matching `CoverImage` fields; all of them make up its `srcset`.
+    #[serde(default = "default_image_presets")]
+    pub presets: Vec<ImagePreset>,
     
     /// Image quality/filter type
     #[serde(default = "default_filter_type")]
     pub filter_type: String,
+    
+    /// Where `cover` presets crop images of posts without a `cover_focus`
+    #[serde(default)]
+    pub crop_focus: CropFocus,
+    
+    /// Extra encodings of the cover and thumbnail, best first (`avif`, `webp`). AVIF is
+    /// opt-in, since it encodes far slower. The source format is always written as the fallback.
+    #[serde(default = "default_image_formats")]
+    pub formats: Vec<String>,
+    
+    /// Encoder quality from 1 to 100 per format (`avif`, `jpeg`, `webp`)
+    #[serde(default = "default_image_quality")]
+    pub quality: BTreeMap<String, u8>,
+    
+    /// BlurHash, LQIP and dominant color shown while the cover loads
+    #[serde(default)]
+    pub placeholders: PlaceholderSettings,
+    
+    /// Source metadata written into the outputs; everything else, like GPS positions, is stripped
+    #[serde(default)]
+    pub keep_metadata: Vec<ImageMetadata>,
+    
+    /// Replaced by the `cover` and `thumbnail` presets; still applied to them, with a warning
+    #[serde(default, skip_serializing)]
+    pub cover_width: Option<u32>,
+    #[serde(default, skip_serializing)]
+    pub cover_height: Option<u32>,
+    #[serde(default, skip_serializing)]
+    pub thumbnail_width: Option<u32>,
+    #[serde(default, skip_serializing)]
+    pub thumbnail_height: Option<u32>,
+}
+
+impl ImageSettings {
+    /// Move the sizes of the removed `cover_*` and `thumbnail_*` keys onto the presets of the
+    /// same name, adding a preset that is missing. Returns a warning per key that was set.
+    pub fn apply_legacy_sizes(&mut self) -> Vec<String> {
+        // With the sizes the removed keys defaulted to
+        let legacy = [
+            ("cover", self.cover_width.take(), self.cover_height.take(), (1200, 800)),
+            ("thumbnail", self.thumbnail_width.take(), self.thumbnail_height.take(), (400, 267)),
+        ];
+        let mut warnings = Vec::new();
+        for (name, width, height, (default_width, default_height)) in legacy {
+            if width.is_none() && height.is_none() {
+                continue;
+            }
+            let preset = match self.presets.iter().position(|preset| preset.name == name) {
+                Some(index) => &mut self.presets[index],
+                None => {
+                    self.presets.push(ImagePreset {
+                        name: name.to_string(),
+                        width: default_width,
+                        height: Some(default_height),
+                        fit: FitMode::Contain,
+                    });
+                    self.presets.last_mut().unwrap()
+                }
+            };
+            // The removed keys scaled the image to fit inside both sizes
+            preset.fit = FitMode::Contain;
+            if let Some(width) = width {
+                preset.width = width;
+                warnings.push(format!("`images.{name}_width` is deprecated, set `width` of the `{name}` preset in `images.presets` instead"));
+            }
+            if let Some(height) = height {
+                preset.height = Some(height);
+                warnings.push(format!("`images.{name}_height` is deprecated, set `height` of the `{name}` preset in `images.presets` instead"));
+            }
+        }
+        warnings
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageMetadata {
+    /// The EXIF Artist and Copyright fields
+    Copyright,
+    /// The embedded ICC color profile
+    Icc,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaceholderSettings {
+    /// Compute placeholders for cover images
+    #[serde(default = "default_placeholders_enabled")]
+    pub enabled: bool,
+    
+    /// BlurHash components across and down, each from 1 to 9
+    #[serde(default = "default_blurhash_components")]
+    pub blurhash_components: [u32; 2],
+    
+    /// Width in pixels of the inline LQIP image
+    #[serde(default = "default_lqip_width")]
+    pub lqip_width: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImagePreset {
+    /// Used in file names, e.g. `intro-photo-w640.jpg`
+    pub name: String,
+    
+    /// Width in pixels
+    pub width: u32,
+    
+    /// Maximum height in pixels; without one the image is scaled to `width`
+    pub height: Option<u32>,
+    
+    /// How the image is fitted into `width` × `height`
+    #[serde(default)]
+    pub fit: FitMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FitMode {
+    /// Scale to fit inside the box, keeping the aspect ratio
+    #[default]
+    Contain,
+    /// Like `contain`, but never enlarge a smaller image
+    ScaleDown,
+    /// Fill `width` × `height` exactly, cropping what does not fit
+    Cover,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CropFocus {
+    /// Keep the middle of the image
+    Center,
+    /// Keep the most detailed part of the image
+    #[default]
+    Entropy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionSettings {
+    /// Collection name, also used for image and asset URLs (e.g. "blog", "projects")
+    pub name: String,
+    
+    /// Input directory containing the collection's markdown files
+    pub content_dir: PathBuf,
+    
+    /// Output directory for the collection's JSON files
+    pub output_dir: PathBuf,
+    
+    /// Frontmatter fields beyond the built-in title, date, description, tags and cover
+    #[serde(default)]
+    pub fields: Vec<FieldSchema>,
+    
+    /// Field to sort the index by (e.g. "date", "title" or a schema field)
+    #[serde(default = "default_sort_by")]
+    pub sort_by: String,
+    
+    /// Sort direction of the index
+    #[serde(default)]
+    pub sort_order: SortOrder,
+    
+    /// Schema fields to include in the index next to the built-in summary fields
+    #[serde(default)]
+    pub index_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldSchema {
+    /// Frontmatter key
+    pub name: String,
+    
+    /// Expected value type
+    #[serde(rename = "type", default)]
+    pub field_type: FieldType,
+    
+    /// Fail the file if the field is missing
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    #[default]
+    String,
+    Number,
+    Boolean,
+    Date,
+    /// A YAML sequence or a comma-separated string, like `tags`
+    List,
+    Url,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SiteSettings {
+    /// Public base URL of the site without trailing slash (e.g. "https://example.com")
+    pub url: Option<String>,
+    
+    /// Site title
+    #[serde(default = "default_site_title")]
+    pub title: String,
+    
+    /// Site description
+    pub description: Option<String>,
+    
+    /// Default author for posts without an author
+    pub author: Option<String>,
+    
+    /// Default language for posts without a `language` field
+    #[serde(default = "default_site_language")]
+    pub language: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeedSettings {
+    /// Write RSS and Atom feeds (requires `site.url`)
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Collections whose posts are included in the feeds
+    #[serde(default = "default_feed_collections")]
+    pub collections: Vec<String>,
+    
+    /// Maximum number of entries per feed
+    #[serde(default = "default_feed_limit")]
+    pub limit: usize,
+    
+    /// Write a separate feed for every language under `feeds/<language>/`
+    #[serde(default = "default_enabled")]
+    pub per_language: bool,
+    
+    /// Write a separate feed for every tag under `feeds/tags/<tag>/`
+    #[serde(default = "default_enabled")]
+    pub per_tag: bool,
+    
+    /// Write a JSON Feed 1.1 (`feed.json`) from the same posts
+    #[serde(default = "default_enabled")]
+    pub json_feed: bool,
+    
+    /// Items per JSON Feed page; further pages are linked through `next_url`
+    #[serde(default = "default_json_feed_page_size")]
+    pub json_feed_page_size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SitemapSettings {
+    /// Write `sitemap.xml` to the public directory (requires `site.url`)
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Routes served by the app itself, listed next to the posts (e.g. "/", "/about")
+    #[serde(default = "default_static_routes")]
+    pub static_routes: Vec<String>,
+    
+    /// Collections whose posts are listed; all collections when empty
+    #[serde(default)]
+    pub collections: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RobotsSettings {
+    /// Write `robots.txt` to the public directory
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Crawler rules, one group per user agent
+    #[serde(default = "default_robots_rules")]
+    pub rules: Vec<RobotsRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RobotsRule {
+    /// User agent the rule applies to (e.g. "*", "GPTBot")
+    pub user_agent: String,
+    
+    /// Allowed path prefixes
+    #[serde(default)]
+    pub allow: Vec<String>,
+    
+    /// Disallowed path prefixes
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchSettings {
+    /// Write a prebuilt search index per language
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Collections whose posts are searchable
+    #[serde(default = "default_feed_collections")]
+    pub collections: Vec<String>,
+    
+    /// Directory below `public_dir` for the `<language>.json` index files
+    #[serde(default = "default_search_dir")]
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OgImageSettings {
+    /// Render a 1200×630 PNG preview card for posts without a cover image
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Directory below `public_dir` for the `<collection>/<slug>.png` cards
+    #[serde(default = "default_og_image_dir")]
+    pub output_dir: PathBuf,
+    
+    /// Optional brand template image, scaled and cropped to fill the card
+    pub background: Option<PathBuf>,
+    
+    /// Background color used when no template image is set
+    #[serde(default = "default_og_background_color")]
+    pub background_color: String,
+    
+    /// Color of the accent bar and tags
+    #[serde(default = "default_og_accent_color")]
+    pub accent_color: String,
+    
+    /// Color of the title, date and site name
+    #[serde(default = "default_og_text_color")]
+    pub text_color: String,
+    
+    /// Share the generated card even when the post has a cover image, so every post gets one
+    #[serde(default)]
+    pub prefer_over_cover: bool,
+    
+    /// Font of the date, tags and site name, read when cards are drawn
+    #[serde(default = "default_og_font")]
+    pub font: PathBuf,
+    
+    /// Font of the title
+    #[serde(default = "default_og_bold_font")]
+    pub bold_font: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrerenderSettings {
+    /// Directory with the Jinja-style HTML templates; templates may extend and include each other
+    #[serde(default = "default_template_dir")]
+    pub template_dir: PathBuf,
+    
+    /// Template for `<output_dir>/<collection>/<slug>/index.html`. No post pages without it.
+    pub post_template: Option<String>,
+    
+    /// Template for `<output_dir>/<collection>/index.html`. No index pages without it.
+    pub index_template: Option<String>,
+    
+    /// The app built by `vite build`, which `site-builder prerender` writes the pages into.
+    /// Not `public_dir`: Vite copies that into the build before the pages can name its files.
+    #[serde(default = "default_prerender_output_dir")]
+    pub output_dir: PathBuf,
+    
+    /// Manifest written by `vite build` (with `build.manifest` on), naming the hashed entry script
+    /// and stylesheets the pages load
+    #[serde(default = "default_vite_manifest")]
+    pub vite_manifest: PathBuf,
+    
+    /// Key of the app entry in the Vite manifest
+    #[serde(default = "default_vite_entry")]
+    pub vite_entry: String,
+    
+    /// Collections to prerender
+    #[serde(default = "default_feed_collections")]
+    pub collections: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaginationSettings {
+    /// Write `index/page-N.json` chunks and `index/manifest.json` for every collection
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Posts per index page
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    
+    /// Also write year and month archive chunks under `index/archive/`
+    #[serde(default)]
+    pub archives: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AssetSettings {
+    /// Publish processed images, bundle resources and Open Graph cards under content-hashed file names
+    #[serde(default)]
+    pub fingerprint: bool,
+    
+    /// Number of hex digits of the SHA-256 hash kept in file names
+    #[serde(default = "default_hash_length")]
+    pub hash_length: usize,
+    
+    /// Manifest below `public_dir` mapping logical asset URLs to hashed ones
+    #[serde(default = "default_asset_manifest")]
+    pub manifest: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LlmsSettings {
+    /// Write `<collection>/<slug>.md` per post and the site-level `llms.txt` files
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Collections whose posts are exported
+    #[serde(default = "default_feed_collections")]
+    pub collections: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CiteSettings {
+    /// Write BibTeX, RIS and CSL-JSON records per post below `<collection>/<slug>/`
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    
+    /// Collections whose posts get records
+    #[serde(default = "default_feed_collections")]
+    pub collections: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutputSettings {
+    /// Write JSON without indentation
+    #[serde(default)]
+    pub compact_json: bool,
+    
+    /// Minify `Post.content` and prerendered pages
+    #[serde(default)]
+    pub minify_html: bool,
+    
+    /// Write `.gz` and `.br` siblings next to matching files in `public_dir`
+    #[serde(default)]
+    pub precompress: bool,
+    
+    /// Extensions of the files to precompress
+    #[serde(default = "default_precompress_extensions")]
+    pub precompress_extensions: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -114,24 +600,145 @@ fn default_language_code() -> String {
     "en-US".to_string()
 }
 
-fn default_cover_width() -> u32 {
-    1200
+fn default_image_presets() -> Vec<ImagePreset> {
+    vec![
+        ImagePreset {
+            name: "thumbnail".to_string(),
+            width: 400,
+            height: Some(267),
+            fit: FitMode::Contain,
+        },
+        ImagePreset {
+            name: "cover".to_string(),
+            width: 1200,
+            height: Some(800),
+            fit: FitMode::Contain,
+        },
+    ]
 }
 
-fn default_cover_height() -> u32 {
-    800
+fn default_filter_type() -> String {
+    "Lanczos3".to_string()
 }
 
-fn default_thumb_width() -> u32 {
-    400
+fn default_image_formats() -> Vec<String> {
+    vec!["webp".to_string()]
 }
 
-fn default_thumb_height() -> u32 {
-    267
+fn default_image_quality() -> BTreeMap<String, u8> {
+    BTreeMap::from([("avif".to_string(), 60), ("jpeg".to_string(), 82), ("webp".to_string(), 80)])
 }
 
-fn default_filter_type() -> String {
-    "Lanczos3".to_string()
+fn default_placeholders_enabled() -> bool {
+    true
+}
+
+fn default_blurhash_components() -> [u32; 2] {
+    [4, 3]
+}
+
+fn default_lqip_width() -> u32 {
+    16
+}
+
+fn default_sort_by() -> String {
+    "date".to_string()
+}
+
+fn default_site_title() -> String {
+    "Blog".to_string()
+}
+
+fn default_site_language() -> String {
+    "en".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_feed_collections() -> Vec<String> {
+    vec!["blog".to_string()]
+}
+
+fn default_feed_limit() -> usize {
+    20
+}
+
+fn default_json_feed_page_size() -> usize {
+    50
+}
+
+fn default_static_routes() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+fn default_robots_rules() -> Vec<RobotsRule> {
+    vec![RobotsRule {
+        user_agent: "*".to_string(),
+        allow: vec!["/".to_string()],
+        disallow: Vec::new(),
+    }]
+}
+
+fn default_search_dir() -> PathBuf {
+    PathBuf::from("search")
+}
+
+fn default_og_image_dir() -> PathBuf {
+    PathBuf::from("images/og")
+}
+
+fn default_og_background_color() -> String {
+    "#07485B".to_string()
+}
+
+fn default_og_accent_color() -> String {
+    "#FF6138".to_string()
+}
+
+fn default_og_text_color() -> String {
+    "#FFFFFF".to_string()
+}
+
+fn default_og_font() -> PathBuf {
+    PathBuf::from("assets/fonts/DejaVuSans.ttf")
+}
+
+fn default_og_bold_font() -> PathBuf {
+    PathBuf::from("assets/fonts/DejaVuSans-Bold.ttf")
+}
+
+fn default_template_dir() -> PathBuf {
+    PathBuf::from("templates")
+}
+
+fn default_prerender_output_dir() -> PathBuf {
+    PathBuf::from("../dist")
+}
+
+fn default_vite_manifest() -> PathBuf {
+    PathBuf::from("../dist/.vite/manifest.json")
+}
+
+fn default_vite_entry() -> String {
+    "index.html".to_string()
+}
+
+fn default_page_size() -> usize {
+    10
+}
+
+fn default_hash_length() -> usize {
+    8
+}
+
+fn default_asset_manifest() -> PathBuf {
+    PathBuf::from("manifest.json")
+}
+
+fn default_precompress_extensions() -> Vec<String> {
+    vec!["json".to_string(), "xml".to_string(), "svg".to_string()]
 }
 
 fn default_cache_dir() -> PathBuf {
@@ -151,6 +758,19 @@ impl Default for Config {
             citations: CitationSettings::default(),
             images: ImageSettings::default(),
             cache: CacheSettings::default(),
+            collections: Vec::new(),
+            site: SiteSettings::default(),
+            feeds: FeedSettings::default(),
+            sitemap: SitemapSettings::default(),
+            robots: RobotsSettings::default(),
+            search: SearchSettings::default(),
+            og_images: OgImageSettings::default(),
+            prerender: PrerenderSettings::default(),
+            pagination: PaginationSettings::default(),
+            assets: AssetSettings::default(),
+            llms: LlmsSettings::default(),
+            cite: CiteSettings::default(),
+            output: OutputSettings::default(),
         }
     }
 }
@@ -169,11 +789,161 @@ impl Default for CitationSettings {
 impl Default for ImageSettings {
     fn default() -> Self {
         Self {
-            cover_width: default_cover_width(),
-            cover_height: default_cover_height(),
-            thumbnail_width: default_thumb_width(),
-            thumbnail_height: default_thumb_height(),
+            presets: default_image_presets(),
             filter_type: default_filter_type(),
+            crop_focus: CropFocus::default(),
+            formats: default_image_formats(),
+            quality: default_image_quality(),
+            placeholders: PlaceholderSettings::default(),
+            keep_metadata: Vec::new(),
+            cover_width: None,
+            cover_height: None,
+            thumbnail_width: None,
+            thumbnail_height: None,
+        }
+    }
+}
+
+impl Default for PlaceholderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_placeholders_enabled(),
+            blurhash_components: default_blurhash_components(),
+            lqip_width: default_lqip_width(),
+        }
+    }
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        Self {
+            url: None,
+            title: default_site_title(),
+            description: None,
+            author: None,
+            language: default_site_language(),
+        }
+    }
+}
+
+impl Default for FeedSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            collections: default_feed_collections(),
+            limit: default_feed_limit(),
+            per_language: default_enabled(),
+            per_tag: default_enabled(),
+            json_feed: default_enabled(),
+            json_feed_page_size: default_json_feed_page_size(),
+        }
+    }
+}
+
+impl Default for SitemapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            static_routes: default_static_routes(),
+            collections: Vec::new(),
+        }
+    }
+}
+
+impl Default for RobotsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            rules: default_robots_rules(),
+        }
+    }
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            collections: default_feed_collections(),
+            output_dir: default_search_dir(),
+        }
+    }
+}
+
+impl Default for OgImageSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            output_dir: default_og_image_dir(),
+            background: None,
+            background_color: default_og_background_color(),
+            accent_color: default_og_accent_color(),
+            text_color: default_og_text_color(),
+            prefer_over_cover: false,
+            font: default_og_font(),
+            bold_font: default_og_bold_font(),
+        }
+    }
+}
+
+impl Default for PrerenderSettings {
+    fn default() -> Self {
+        Self {
+            template_dir: default_template_dir(),
+            post_template: None,
+            index_template: None,
+            output_dir: default_prerender_output_dir(),
+            vite_manifest: default_vite_manifest(),
+            vite_entry: default_vite_entry(),
+            collections: default_feed_collections(),
+        }
+    }
+}
+
+impl Default for PaginationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            page_size: default_page_size(),
+            archives: false,
+        }
+    }
+}
+
+impl Default for AssetSettings {
+    fn default() -> Self {
+        Self {
+            fingerprint: false,
+            hash_length: default_hash_length(),
+            manifest: default_asset_manifest(),
+        }
+    }
+}
+
+impl Default for LlmsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            collections: default_feed_collections(),
+        }
+    }
+}
+
+impl Default for CiteSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            collections: default_feed_collections(),
+        }
+    }
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            compact_json: false,
+            minify_html: false,
+            precompress: false,
+            precompress_extensions: default_precompress_extensions(),
         }
     }
 }
@@ -199,6 +969,15 @@ impl Config {
             let mut config: Config = serde_yaml::from_str(&content)
                 .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
             
+            for warning in config.images.apply_legacy_sizes() {
+                eprintln!("⚠️  {warning}");
+            }
+            
+            for collection in &config.collections {
+                crate::collections::check_schema(collection)
+                    .with_context(|| format!("Invalid collection in {}", path.display()))?;
+            }
+            
             // Override Zotero settings with environment variables if present
             if let Some(ref mut zotero) = config.citations.zotero {
                 if let Ok(api_key) = std::env::var("ZOTERO_API_KEY") {
@@ -237,6 +1016,7 @@ impl Config {
     }
     
     /// Save configuration to a YAML file
+    #[allow(dead_code)]
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
         let content = serde_yaml::to_string(self)
@@ -248,6 +1028,34 @@ impl Config {
         Ok(())
     }
     
+    /// Collections to build, falling back to a single `blog` collection
+    /// from `content_dir` and `output_dir` when none are configured
+    pub fn collections(&self) -> Vec<CollectionSettings> {
+        if !self.collections.is_empty() {
+            return self.collections.clone();
+        }
+        
+        vec![CollectionSettings {
+            name: "blog".to_string(),
+            content_dir: self.content_dir.clone(),
+            output_dir: self.output_dir.clone(),
+            fields: Vec::new(),
+            sort_by: default_sort_by(),
+            sort_order: SortOrder::default(),
+            index_fields: Vec::new(),
+        }]
+    }
+    
+    /// Absolute URL for a site path such as `/blog/intro`, if `site.url` is set
+    pub fn absolute_url(&self, path: &str) -> Option<String> {
+        let base = self.site.url.as_deref()?.trim_end_matches('/');
+        if path.starts_with("http://") || path.starts_with("https://") {
+            Some(path.to_string())
+        } else {
+            Some(format!("{base}/{}", path.trim_start_matches('/')))
+        }
+    }
+    
     /// Get Zotero configuration as a tuple for compatibility with existing code
     pub fn get_zotero_config(&self) -> Option<(String, String, Option<String>)> {
         self.citations.zotero.as_ref().and_then(|z| {
@@ -0,0 +1,514 @@
+use anyhow::{bail, Context, Result};
+use html_escape::{encode_double_quoted_attribute as attr, encode_text as text};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::config::Config;
+use crate::feeds::absolutize_urls;
+use crate::{CollectionBuild, Post};
+
+/// Theme for the code highlighting classes emitted by `render_code_block`
+const CODE_THEME: &str = "InspiredGitHub";
+
+const BASE_CSS: &str = "body { font-family: serif; line-height: 1.5; }
+h1, h2, h3 { font-family: sans-serif; line-height: 1.2; }
+img { max-width: 100%; height: auto; }
+pre { white-space: pre-wrap; font-size: 0.85em; padding: 0.5em; }
+.date { font-style: italic; }
+.citation { text-decoration: none; }
+.reference-item { margin-bottom: 0.5em; padding-left: 1.5em; text-indent: -1.5em; }
+";
+
+/// Which posts go into the book and where it is written
+pub struct EpubOptions<'a> {
+    pub collection: &'a str,
+    /// Posts whose `series` frontmatter matches
+    pub series: Option<&'a str>,
+    /// Posts with this tag
+    pub tag: Option<&'a str>,
+    /// Posts by slug, in this order. Overrides `series` and `tag`.
+    pub slugs: &'a [String],
+    /// Book title, defaults to the series name or the site title
+    pub title: Option<&'a str>,
+    pub output: &'a Path,
+}
+
+struct Chapter<'a> {
+    file: String,
+    post: &'a Post,
+    body: String,
+}
+
+/// Images copied from `public_dir` into the book, keyed by their site URL
+#[derive(Default)]
+struct Images {
+    files: Vec<(String, String)>,
+    by_url: HashMap<String, String>,
+}
+
+impl Images {
+    /// File name inside the book for an image URL, `None` if it is not a published file
+    fn embed(&mut self, config: &Config, url: &str) -> Option<String> {
+        if let Some(file) = self.by_url.get(url) {
+            return Some(file.clone());
+        }
+        let ext = Path::new(url).extension()?.to_str()?.to_lowercase();
+        media_type(&ext)?;
+        if !config.public_dir.join(url.trim_start_matches('/')).is_file() {
+            return None;
+        }
+        let file = format!("images/image-{}.{ext}", self.files.len() + 1);
+        self.files.push((url.to_string(), file.clone()));
+        self.by_url.insert(url.to_string(), file.clone());
+        Some(file)
+    }
+}
+
+/// Assemble the selected posts into an EPUB 3 file with a navigation document, embedded
+/// images, a code stylesheet and one combined references chapter. Returns the number of chapters.
+pub fn write_epub(config: &Config, builds: &[CollectionBuild], options: &EpubOptions) -> Result<usize> {
+    let posts = select_posts(builds, options)?;
+    let title = options
+        .title
+        .or(options.series)
+        .unwrap_or(&config.site.title);
+    let language = posts[0]
+        .language
+        .as_deref()
+        .unwrap_or(&config.site.language);
+
+    let image_regex = regex_lite::Regex::new(r#"src="(/[^"]+)""#).unwrap();
+
+    let mut images = Images::default();
+    let mut references: Vec<(String, String)> = Vec::new();
+    let mut seen_references = HashSet::new();
+    let mut chapters = Vec::new();
+
+    for (i, post) in posts.iter().enumerate() {
+        let xhtml = to_xhtml(&post.content);
+        // The citation pipeline appends the post's reference list to its content
+        let (content, post_references) = match xhtml.find("<div class=\"references\">") {
+            Some(start) => xhtml.split_at(start),
+            None => (xhtml.as_str(), ""),
+        };
+        let post_references = reference_items(post_references)
+            .with_context(|| format!("Failed to read the references of `{}`", post.slug))?;
+        for (key, entry) in post_references {
+            if seen_references.insert(key.clone()) {
+                references.push((key, entry));
+            }
+        }
+
+        let content = content.replace("href=\"#ref-", "href=\"references.xhtml#ref-");
+        let content = image_regex.replace_all(&content, |caps: &regex_lite::Captures| {
+            match images.embed(config, &caps[1]) {
+                Some(file) => format!("src=\"{file}\""),
+                None => caps[0].to_string(),
+            }
+        });
+        // Links to the site only work online
+        let content = match &config.site.url {
+            Some(url) => absolutize_urls(&content, url),
+            None => content.into_owned(),
+        };
+
+        let mut body = format!(
+            "<h1>{}</h1>\n<p class=\"date\">{}</p>\n",
+            text(&post.title),
+            post.date.format("%B %-d, %Y")
+        );
+        if let Some(file) = post.cover.as_ref().and_then(|c| images.embed(config, &c.cover)) {
+            body.push_str(&format!("<img src=\"{file}\" alt=\"\" />\n"));
+        }
+        body.push_str(&content);
+
+        chapters.push(Chapter {
+            file: format!("chapter-{}.xhtml", i + 1),
+            post,
+            body,
+        });
+    }
+
+    let file = File::create(options.output)
+        .with_context(|| format!("Failed to create EPUB file: {}", options.output.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The mimetype must come first and uncompressed so readers can sniff the format
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+
+    let mut add = |name: &str, content: &[u8]| -> Result<()> {
+        zip.start_file(format!("OEBPS/{name}"), deflated)?;
+        zip.write_all(content)
+            .with_context(|| format!("Failed to write {name} to {}", options.output.display()))
+    };
+
+    add("styles.css", stylesheet()?.as_bytes())?;
+    for chapter in &chapters {
+        let page = xhtml_page(&chapter.post.title, chapter.post.language.as_deref().unwrap_or(language), &format!(
+            "<section epub:type=\"chapter\">\n{}\n</section>",
+            chapter.body
+        ));
+        check_xml(&page).with_context(|| format!("`{}` is not valid XHTML", chapter.post.slug))?;
+        add(&chapter.file, page.as_bytes())?;
+    }
+    if !references.is_empty() {
+        let items: String = references
+            .iter()
+            .map(|(key, entry)| format!("<div id=\"ref-{key}\" class=\"reference-item\">{entry}</div>\n"))
+            .collect();
+        let page = xhtml_page("References", language, &format!(
+            "<section epub:type=\"bibliography\">\n<h1>References</h1>\n{items}</section>"
+        ));
+        check_xml(&page).context("The references are not valid XHTML")?;
+        add("references.xhtml", page.as_bytes())?;
+    }
+    add("nav.xhtml", nav_document(title, language, &chapters, !references.is_empty()).as_bytes())?;
+    for (url, file) in &images.files {
+        let path = config.public_dir.join(url.trim_start_matches('/'));
+        let content = fs::read(&path)
+            .with_context(|| format!("Failed to read image for EPUB: {}", path.display()))?;
+        add(file, &content)?;
+    }
+    add("content.opf", package_document(config, title, language, &chapters, &images, !references.is_empty()).as_bytes())?;
+
+    zip.finish()
+        .with_context(|| format!("Failed to finish EPUB file: {}", options.output.display()))?;
+    Ok(chapters.len())
+}
+
+/// Posts in the order they appear in the book: the given slugs as listed, otherwise oldest first
+fn select_posts<'a>(builds: &'a [CollectionBuild], options: &EpubOptions) -> Result<Vec<&'a Post>> {
+    let Some(build) = builds.iter().find(|b| b.collection.name == options.collection) else {
+        bail!("Unknown collection: {}", options.collection);
+    };
+
+    let posts: Vec<&Post> = if options.slugs.is_empty() {
+        let mut posts: Vec<&Post> = build
+            .posts
+            .iter()
+            .filter(|post| options.series.is_none_or(|series| post.series.as_deref() == Some(series)))
+            .filter(|post| options.tag.is_none_or(|tag| post.tags.iter().any(|t| t == tag)))
+            .collect();
+        posts.sort_by_key(|post| post.date);
+        posts
+    } else {
+        options
+            .slugs
+            .iter()
+            .map(|slug| {
+                build
+                    .posts
+                    .iter()
+                    .find(|post| &post.slug == slug)
+                    .with_context(|| format!("No post with slug `{slug}` in {}", options.collection))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    if posts.is_empty() {
+        bail!("No posts in {} match the selection", options.collection);
+    }
+    Ok(posts)
+}
+
+/// Rewrite the HTML5 that markdown and raw HTML produce into XHTML: void elements are
+/// closed, named entities become numeric references and stray `&` are escaped
+fn to_xhtml(html: &str) -> String {
+    let void_regex = regex_lite::Regex::new(
+        r"<(area|base|br|col|embed|hr|img|input|link|meta|source|track|wbr)\b([^>]*?)\s*/?>",
+    )
+    .unwrap();
+    let entity_regex = regex_lite::Regex::new(r"&(#[0-9]+;|#x[0-9a-fA-F]+;|[A-Za-z][A-Za-z0-9]*;)?").unwrap();
+
+    let html = void_regex.replace_all(html, "<$1$2 />");
+    entity_regex
+        .replace_all(&html, |caps: &regex_lite::Captures| match caps.get(1).map(|m| m.as_str()) {
+            Some("amp;" | "lt;" | "gt;" | "quot;" | "apos;") => caps[0].to_string(),
+            Some(reference) if reference.starts_with('#') => caps[0].to_string(),
+            Some(_) => match html_escape::decode_html_entities(&caps[0]) {
+                decoded if decoded != caps[0] => decoded.chars().map(|c| format!("&#{};", c as u32)).collect(),
+                _ => format!("&amp;{}", &caps[0][1..]),
+            },
+            None => "&amp;".to_string(),
+        })
+        .into_owned()
+}
+
+/// Make sure a page is well-formed XML, which EPUB readers require
+fn check_xml(page: &str) -> Result<()> {
+    let mut reader = Reader::from_str(page);
+    let mut open = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => open.push(e.name().as_ref().to_vec()),
+            Ok(Event::End(_)) => {
+                open.pop();
+            }
+            Ok(Event::Text(e)) => {
+                e.unescape()
+                    .with_context(|| format!("Invalid text at byte {}", reader.buffer_position()))?;
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => bail!("{e} at byte {}", reader.error_position()),
+        }
+    }
+    if let Some(name) = open.pop() {
+        bail!("Unclosed <{}> element", String::from_utf8_lossy(&name));
+    }
+    Ok(())
+}
+
+/// Key and inner markup of every `reference-item` in a reference list
+fn reference_items(xhtml: &str) -> Result<Vec<(String, String)>> {
+    let mut reader = Reader::from_str(xhtml);
+    let mut items = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"div" => {
+                let class = e.try_get_attribute("class")?;
+                let id = e.try_get_attribute("id")?;
+                let key = id.as_ref().map(|id| id.unescape_value()).transpose()?;
+                let is_item = class.is_some_and(|class| class.value.as_ref() == b"reference-item");
+                if let (true, Some(key)) = (is_item, key.as_deref().and_then(|id| id.strip_prefix("ref-"))) {
+                    let key = key.to_string();
+                    let span = reader.read_to_end(e.name())?;
+                    items.push((key, xhtml[span.start as usize..span.end as usize].to_string()));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(items)
+}
+
+fn stylesheet() -> Result<String> {
+    let themes = ThemeSet::load_defaults();
+    let theme = themes
+        .themes
+        .get(CODE_THEME)
+        .with_context(|| format!("Missing syntax theme: {CODE_THEME}"))?;
+    let code_css = css_for_theme_with_class_style(theme, ClassStyle::Spaced)
+        .context("Failed to generate code stylesheet")?;
+    Ok(format!("{BASE_CSS}\n{code_css}"))
+}
+
+fn xhtml_page(title: &str, language: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{language}" xml:lang="{language}">
+<head>
+<meta charset="UTF-8" />
+<title>{}</title>
+<link rel="stylesheet" type="text/css" href="styles.css" />
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        text(title),
+        language = attr(language)
+    )
+}
+
+fn nav_document(title: &str, language: &str, chapters: &[Chapter], has_references: bool) -> String {
+    let mut items: Vec<String> = chapters
+        .iter()
+        .map(|c| format!("<li><a href=\"{}\">{}</a></li>", c.file, text(&c.post.title)))
+        .collect();
+    if has_references {
+        items.push("<li><a href=\"references.xhtml\">References</a></li>".to_string());
+    }
+    xhtml_page(
+        title,
+        language,
+        &format!(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n<ol>\n{}\n</ol>\n</nav>",
+            text(title),
+            items.join("\n")
+        ),
+    )
+}
+
+fn package_document(
+    config: &Config,
+    title: &str,
+    language: &str,
+    chapters: &[Chapter],
+    images: &Images,
+    has_references: bool,
+) -> String {
+    // Stable across exports of the same selection, so readers keep reading positions
+    let mut hasher = Sha256::new();
+    hasher.update(title);
+    for chapter in chapters {
+        hasher.update(&chapter.post.slug);
+    }
+    let identifier = format!("urn:site-builder:{:x}", hasher.finalize());
+    let modified = chapters
+        .iter()
+        .map(|c| c.post.updated.unwrap_or(c.post.date))
+        .max()
+        .unwrap_or_default();
+    let cover = chapters
+        .first()
+        .and_then(|c| c.post.cover.as_ref())
+        .and_then(|c| images.by_url.get(&c.cover));
+
+    let mut manifest = vec![
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />".to_string(),
+        "<item id=\"css\" href=\"styles.css\" media-type=\"text/css\" />".to_string(),
+    ];
+    let mut spine = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        manifest.push(format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\" />",
+            i + 1,
+            chapter.file
+        ));
+        spine.push(format!("<itemref idref=\"chapter-{}\" />", i + 1));
+    }
+    if has_references {
+        manifest.push("<item id=\"references\" href=\"references.xhtml\" media-type=\"application/xhtml+xml\" />".to_string());
+        spine.push("<itemref idref=\"references\" />".to_string());
+    }
+    for (i, (_, file)) in images.files.iter().enumerate() {
+        let ext = file.rsplit('.').next().unwrap_or_default();
+        let properties = if Some(file) == cover { " properties=\"cover-image\"" } else { "" };
+        manifest.push(format!(
+            "<item id=\"image-{}\" href=\"{file}\" media-type=\"{}\"{properties} />",
+            i + 1,
+            media_type(ext).unwrap_or("application/octet-stream")
+        ));
+    }
+
+    let creator = config
+        .site
+        .author
+        .as_deref()
+        .map(|author| format!("\n    <dc:creator>{}</dc:creator>", text(author)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{language}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{}</dc:title>
+    <dc:language>{language}</dc:language>{creator}
+    <meta property="dcterms:modified">{}</meta>
+  </metadata>
+  <manifest>
+    {}
+  </manifest>
+  <spine>
+    {}
+  </spine>
+</package>
+"#,
+        text(title),
+        modified.format("%Y-%m-%dT%H:%M:%SZ"),
+        manifest.join("\n    "),
+        spine.join("\n    "),
+        language = attr(language)
+    )
+}
+
+fn media_type(ext: &str) -> Option<&'static str> {
+    match ext {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml" />
+  </rootfiles>
+</container>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn post(slug: &str, date: &str, content: &str) -> Post {
+        serde_json::from_value(serde_json::json!({
+            "title": slug,
+            "date": date,
+            "slug": slug,
+            "content": content,
+            "series": "rust",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_epub_combines_references_and_orders_chapters() {
+        let config = Config::default();
+        let reference = "<div class=\"references\">\n<h2>References</h2>\n<div class=\"references-list\">\n<div id=\"ref-knuth\" class=\"reference-item\">Knuth, D. (1984).\n<div><i>Literate&nbsp;Programming</i></div></div>\n</div>\n</div>\n";
+        let builds = vec![CollectionBuild {
+            collection: config.collections().remove(0),
+            posts: vec![
+                post("second", "2024-02-01T00:00:00Z", &format!("<p><a href=\"#ref-knuth\" class=\"citation\">(Knuth, 1984)</a></p>\n{reference}")),
+                post("first", "2024-01-01T00:00:00Z", &format!("<p>Intro<br>Q&A</p>\n<hr>\n{reference}")),
+            ],
+            processed: 2,
+            skipped: 0,
+            errors: Vec::new(),
+        }];
+        let output = std::env::temp_dir().join(format!("site-builder-test-{}.epub", std::process::id()));
+        let options = EpubOptions {
+            collection: "blog",
+            series: Some("rust"),
+            tag: None,
+            slugs: &[],
+            title: None,
+            output: &output,
+        };
+        assert_eq!(write_epub(&config, &builds, &options).unwrap(), 2);
+
+        let mut archive = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+            content
+        };
+        for name in ["OEBPS/chapter-1.xhtml", "OEBPS/chapter-2.xhtml", "OEBPS/references.xhtml", "OEBPS/nav.xhtml"] {
+            check_xml(&read(name)).unwrap();
+        }
+        assert!(read("OEBPS/chapter-1.xhtml").contains("<h1>first</h1>"));
+        assert!(read("OEBPS/chapter-1.xhtml").contains("<p>Intro<br />Q&amp;A</p>\n<hr />"));
+        assert!(read("OEBPS/chapter-2.xhtml").contains("href=\"references.xhtml#ref-knuth\""));
+        assert!(!read("OEBPS/chapter-2.xhtml").contains("references-list"));
+        assert_eq!(read("OEBPS/references.xhtml").matches("id=\"ref-knuth\"").count(), 1);
+        assert!(read("OEBPS/references.xhtml").contains("<i>Literate&#160;Programming</i></div></div>"));
+        assert!(check_xml("<p>Unclosed").is_err());
+        assert!(check_xml("<p>&nbsp;</p>").is_err());
+        fs::remove_file(output).unwrap();
+    }
+}
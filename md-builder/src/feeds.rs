@@ -0,0 +1,347 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use html_escape::{encode_double_quoted_attribute as attr, encode_text as text};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::{is_indexed, post_language, post_path, CollectionBuild, Post};
+
+/// A post together with the absolute URL it is published at
+pub struct FeedEntry<'a> {
+    pub post: &'a Post,
+    pub url: String,
+}
+
+struct Feed<'a> {
+    /// Directory below `public_dir` the feed files are written to
+    dir: PathBuf,
+    title: String,
+    language: String,
+    entries: Vec<&'a FeedEntry<'a>>,
+}
+
+/// Indexed posts of the feed collections with their absolute URLs, newest first.
+/// Returns an empty list when `site.url` is not configured.
+pub fn feed_entries<'a>(config: &Config, builds: &'a [CollectionBuild]) -> Vec<FeedEntry<'a>> {
+    let mut entries: Vec<FeedEntry> = builds
+        .iter()
+        .filter(|b| config.feeds.collections.contains(&b.collection.name))
+        .flat_map(|b| {
+            b.posts.iter().filter(|post| is_indexed(post)).filter_map(|post| {
+                let url = config.absolute_url(&post_path(&b.collection.name, &post.slug))?;
+                Some(FeedEntry { post, url })
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.post.date));
+    entries
+}
+
+/// Write `rss.xml` and `atom.xml` for all posts, and optionally for every language and tag.
+/// Returns the number of feeds written.
+pub fn write_feeds(config: &Config, builds: &[CollectionBuild]) -> Result<usize> {
+    if !config.feeds.enabled {
+        return Ok(0);
+    }
+    if config.site.url.is_none() {
+        eprintln!("⚠️  Skipping RSS and Atom feeds: `site.url` is not set");
+        return Ok(0);
+    }
+
+    let entries = feed_entries(config, builds);
+    let site_language = config.site.language.clone();
+
+    let mut feeds = vec![Feed {
+        dir: PathBuf::new(),
+        title: config.site.title.clone(),
+        language: site_language.clone(),
+        entries: entries.iter().collect(),
+    }];
+
+    if config.feeds.per_language {
+        let mut by_language: BTreeMap<String, Vec<&FeedEntry>> = BTreeMap::new();
+        for entry in &entries {
+            by_language.entry(post_language(config, entry.post).to_string()).or_default().push(entry);
+        }
+        for (language, entries) in by_language {
+            feeds.push(Feed {
+                dir: Path::new("feeds").join(&language),
+                title: format!("{} ({language})", config.site.title),
+                language,
+                entries,
+            });
+        }
+    }
+
+    if config.feeds.per_tag {
+        for (tag_slug, (tag, entries)) in group_by_tag(&entries) {
+            feeds.push(Feed {
+                dir: Path::new("feeds").join("tags").join(tag_slug),
+                title: format!("{}: {tag}", config.site.title),
+                language: site_language.clone(),
+                entries,
+            });
+        }
+    }
+    remove_stale_tag_feeds(&config.public_dir.join("feeds").join("tags"), &feeds)?;
+
+    for feed in &mut feeds {
+        feed.entries.truncate(config.feeds.limit);
+        let dir = config.public_dir.join(&feed.dir);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create feed directory: {}", dir.display()))?;
+
+        let rss_path = dir.join("rss.xml");
+        fs::write(&rss_path, render_rss(config, feed))
+            .with_context(|| format!("Failed to write RSS feed: {}", rss_path.display()))?;
+
+        let atom_path = dir.join("atom.xml");
+        fs::write(&atom_path, render_atom(config, feed))
+            .with_context(|| format!("Failed to write Atom feed: {}", atom_path.display()))?;
+    }
+
+    Ok(feeds.len())
+}
+
+fn render_rss(config: &Config, feed: &Feed) -> String {
+    let site_url = config.absolute_url("/").unwrap_or_default();
+    let self_url = feed_url(config, &feed.dir, "rss.xml");
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:media=\"http://search.yahoo.com/mrss/\">\n");
+    xml.push_str("<channel>\n");
+    xml.push_str(&format!("  <title>{}</title>\n", text(&feed.title)));
+    xml.push_str(&format!("  <link>{}</link>\n", text(&site_url)));
+    xml.push_str(&format!(
+        "  <description>{}</description>\n",
+        text(config.site.description.as_deref().unwrap_or(&config.site.title))
+    ));
+    xml.push_str(&format!("  <language>{}</language>\n", text(&feed.language)));
+    if let Some(updated) = last_updated(feed) {
+        xml.push_str(&format!("  <lastBuildDate>{}</lastBuildDate>\n", updated.to_rfc2822()));
+    }
+    xml.push_str(&format!(
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\" />\n",
+        attr(&self_url)
+    ));
+
+    for entry in &feed.entries {
+        let post = entry.post;
+        xml.push_str("  <item>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", text(&post.title)));
+        xml.push_str(&format!("    <link>{}</link>\n", text(&entry.url)));
+        xml.push_str(&format!("    <guid isPermaLink=\"true\">{}</guid>\n", text(&entry.url)));
+        xml.push_str(&format!("    <pubDate>{}</pubDate>\n", post.date.to_rfc2822()));
+        if let Some(author) = &config.site.author {
+            xml.push_str(&format!("    <dc:creator>{}</dc:creator>\n", text(author)));
+        }
+        if let Some(description) = &post.description {
+            xml.push_str(&format!("    <description>{}</description>\n", text(description)));
+        }
+        for tag in &post.tags {
+            xml.push_str(&format!("    <category>{}</category>\n", text(tag)));
+        }
+        if let Some(image) = post.cover.as_ref().and_then(|c| config.absolute_url(&c.cover)) {
+            xml.push_str(&format!("    <media:content url=\"{}\" medium=\"image\" />\n", attr(&image)));
+        }
+        xml.push_str(&format!(
+            "    <content:encoded>{}</content:encoded>\n",
+            text(&absolutize_urls(&post.content, &site_url))
+        ));
+        xml.push_str("  </item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn render_atom(config: &Config, feed: &Feed) -> String {
+    let site_url = config.absolute_url("/").unwrap_or_default();
+    let self_url = feed_url(config, &feed.dir, "atom.xml");
+    let updated = last_updated(feed).unwrap_or_default();
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"{}\">\n",
+        attr(&feed.language)
+    ));
+    xml.push_str(&format!("  <id>{}</id>\n", text(&self_url)));
+    xml.push_str(&format!("  <title>{}</title>\n", text(&feed.title)));
+    if let Some(description) = &config.site.description {
+        xml.push_str(&format!("  <subtitle>{}</subtitle>\n", text(description)));
+    }
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated.to_rfc3339()));
+    xml.push_str(&format!("  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\" />\n", attr(&self_url)));
+    xml.push_str(&format!("  <link rel=\"alternate\" type=\"text/html\" href=\"{}\" />\n", attr(&site_url)));
+    if let Some(author) = &config.site.author {
+        xml.push_str(&format!("  <author><name>{}</name></author>\n", text(author)));
+    }
+
+    for entry in &feed.entries {
+        let post = entry.post;
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", text(&entry.url)));
+        xml.push_str(&format!("    <title>{}</title>\n", text(&post.title)));
+        xml.push_str(&format!("    <link rel=\"alternate\" type=\"text/html\" href=\"{}\" />\n", attr(&entry.url)));
+        xml.push_str(&format!("    <published>{}</published>\n", post.date.to_rfc3339()));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            post.updated.unwrap_or(post.date).to_rfc3339()
+        ));
+        if let Some(description) = &post.description {
+            xml.push_str(&format!("    <summary>{}</summary>\n", text(description)));
+        }
+        for tag in &post.tags {
+            xml.push_str(&format!("    <category term=\"{}\" />\n", attr(tag)));
+        }
+        if let Some(image) = post.cover.as_ref().and_then(|c| config.absolute_url(&c.cover)) {
+            xml.push_str(&format!("    <link rel=\"enclosure\" href=\"{}\" />\n", attr(&image)));
+        }
+        xml.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            text(&absolutize_urls(&post.content, &site_url))
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn feed_url(config: &Config, dir: &Path, file: &str) -> String {
+    let path = crate::sections::path_to_slug(&dir.join(file));
+    config.absolute_url(&path).unwrap_or_default()
+}
+
+fn last_updated(feed: &Feed) -> Option<DateTime<Utc>> {
+    feed.entries
+        .iter()
+        .map(|entry| entry.post.updated.unwrap_or(entry.post.date))
+        .max()
+}
+
+/// Rewrite root-relative `src` and `href` attributes so content works outside the site
+pub fn absolutize_urls(html: &str, site_url: &str) -> String {
+    let base = site_url.trim_end_matches('/');
+    let url_regex = regex_lite::Regex::new(r#"(src|href)="/([^/"][^"]*)?""#).unwrap();
+    url_regex
+        .replace_all(html, |caps: &regex_lite::Captures| {
+            format!("{}=\"{base}/{}\"", &caps[1], caps.get(2).map_or("", |m| m.as_str()))
+        })
+        .into_owned()
+}
+
+/// Entries grouped by tag slug, keyed to the first spelling of the tag. Tags without
+/// a usable slug and tags whose slug is taken by a different tag are skipped.
+fn group_by_tag<'a>(entries: &'a [FeedEntry<'a>]) -> BTreeMap<String, (String, Vec<&'a FeedEntry<'a>>)> {
+    let mut by_tag: BTreeMap<String, (String, Vec<&FeedEntry>)> = BTreeMap::new();
+    let mut skipped = BTreeSet::new();
+    for entry in entries {
+        for tag in &entry.post.tags {
+            let tag_slug = slugify(tag);
+            if tag_slug.is_empty() {
+                if skipped.insert(tag.clone()) {
+                    eprintln!("⚠️  Skipping feed for tag `{tag}`: it has no letters or digits");
+                }
+                continue;
+            }
+            let (first, tagged) = by_tag.entry(tag_slug).or_insert_with(|| (tag.clone(), Vec::new()));
+            if first.trim().to_lowercase() != tag.trim().to_lowercase() {
+                if skipped.insert(tag.clone()) {
+                    eprintln!("⚠️  Skipping feed for tag `{tag}`: its slug is already used by `{first}`");
+                }
+                continue;
+            }
+            tagged.push(entry);
+        }
+    }
+    by_tag
+}
+
+/// Remove tag feed directories left over from tags no post uses anymore
+fn remove_stale_tag_feeds(tags_dir: &Path, feeds: &[Feed]) -> Result<()> {
+    let Ok(dirs) = fs::read_dir(tags_dir) else {
+        return Ok(());
+    };
+    for dir in dirs.flatten() {
+        let path = dir.path();
+        let feed_dir = Path::new("feeds").join("tags").join(dir.file_name());
+        let current = feeds.iter().any(|feed| feed.dir == feed_dir);
+        if path.is_dir() && !current {
+            fs::remove_dir_all(&path)
+                .with_context(|| format!("Failed to remove stale tag feed: {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Lowercase, URL-safe form of a tag for file and directory names
+pub fn slugify(value: &str) -> String {
+    let mut slug = String::new();
+    for c in value.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_slugs_and_absolute_urls() {
+        assert_eq!(slugify("Machine Learning"), "machine-learning");
+        assert_eq!(slugify(" C++ / Rust "), "c-rust");
+        assert_eq!(slugify("Havbruk & Laks"), "havbruk-laks");
+        assert_eq!(slugify("#"), "");
+
+        let html = r#"<img src="/images/a.png" /><a href="//cdn.example.com/x">x</a><a href="/">home</a>"#;
+        assert_eq!(
+            absolutize_urls(html, "https://example.com/"),
+            r#"<img src="https://example.com/images/a.png" /><a href="//cdn.example.com/x">x</a><a href="https://example.com/">home</a>"#
+        );
+    }
+
+    #[test]
+    fn test_tag_feeds_skip_empty_and_colliding_slugs() {
+        let post = |tags: &[&str]| Post {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        };
+        let posts = [post(&["C++", "Rust"]), post(&["c", "rust", "#"])];
+        let entries: Vec<FeedEntry> = posts
+            .iter()
+            .map(|post| FeedEntry { post, url: String::new() })
+            .collect();
+        let by_tag = group_by_tag(&entries);
+        assert_eq!(by_tag.keys().collect::<Vec<_>>(), ["c", "rust"]);
+        assert_eq!(by_tag["c"].0, "C++");
+        assert_eq!(by_tag["c"].1.len(), 1);
+        assert_eq!(by_tag["rust"].1.len(), 2);
+
+        let tags_dir = std::env::temp_dir()
+            .join(format!("site-builder-tag-feeds-{}", std::process::id()))
+            .join("feeds")
+            .join("tags");
+        fs::create_dir_all(tags_dir.join("rust")).unwrap();
+        fs::create_dir_all(tags_dir.join("python")).unwrap();
+        let feeds = [Feed {
+            dir: Path::new("feeds").join("tags").join("rust"),
+            title: String::new(),
+            language: String::new(),
+            entries: Vec::new(),
+        }];
+        remove_stale_tag_feeds(&tags_dir, &feeds).unwrap();
+        assert!(tags_dir.join("rust").exists());
+        assert!(!tags_dir.join("python").exists());
+        fs::remove_dir_all(tags_dir.parent().unwrap().parent().unwrap()).unwrap();
+    }
+}
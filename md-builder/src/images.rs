@@ -0,0 +1,1438 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{
+    ColorType, DynamicImage, GenericImageView, GrayImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader, RgbImage,
+};
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+use crate::assets::AssetManifest;
+use crate::config::{Config, CropFocus, FitMode, ImageMetadata, ImagePreset};
+use crate::{blurhash, should_process_file, CoverImage};
+
+/// Bumped when the encoders change, so every output is written again
+const ENCODING_VERSION: u32 = 1;
+/// rav1e speed from 1 (slowest, smallest files) to 10
+const AVIF_SPEED: u8 = 6;
+const DEFAULT_AVIF_QUALITY: u8 = 60;
+const DEFAULT_JPEG_QUALITY: u8 = 82;
+const DEFAULT_WEBP_QUALITY: u8 = 80;
+/// Longest side of the downscaled copy that entropy crops are chosen on
+const ENTROPY_SAMPLE_SIZE: u32 = 256;
+/// Crop windows compared along the free axis of an entropy crop
+const ENTROPY_CANDIDATES: u32 = 32;
+/// Longest side of the downscaled copy that BlurHash and the dominant color are computed on
+const PLACEHOLDER_SAMPLE_SIZE: u32 = 64;
+/// EXIF Artist and Copyright tags, kept with `copyright` in `images.keep_metadata`
+const COPYRIGHT_TAGS: [u16; 2] = [0x013B, 0x8298];
+/// EXIF Orientation tag, kept in JPEG originals that are not re-encoded upright
+const ORIENTATION_TAG: u16 = 0x0112;
+/// EXIF field types of NUL-terminated text and of 16-bit numbers
+const EXIF_ASCII: u16 = 2;
+const EXIF_SHORT: u16 = 3;
+/// Flags in the extended WebP header saying that an ICC profile or EXIF chunk follows
+const WEBP_ICC_FLAG: u8 = 0x20;
+const WEBP_EXIF_FLAG: u8 = 0x08;
+const WEBP_XMP_FLAG: u8 = 0x04;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Suffixes of the fixed cover and thumbnail outputs written before presets
+const LEGACY_SUFFIXES: [&str; 2] = ["-cover", "-thumb"];
+
+/// Pixel size of an image file, for the `width` and `height` attributes that reserve its space
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+pub struct ImageSize {
+    pub width: u32,
+    pub height: u32,
+    /// `width / height`, rounded to four decimals
+    pub aspect_ratio: f64,
+}
+
+impl ImageSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        let aspect_ratio = f64::from(width) / f64::from(height.max(1));
+        ImageSize {
+            width,
+            height,
+            aspect_ratio: (aspect_ratio * 10_000.0).round() / 10_000.0,
+        }
+    }
+
+    /// Size of an image file as shown, read from its header with the EXIF orientation that
+    /// JPEG originals keep
+    fn read(path: &Path) -> Result<Self> {
+        let mut decoder = ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(image::ImageError::IoError)
+            .and_then(|reader| reader.into_decoder())
+            .with_context(|| format!("Failed to read image dimensions: {}", path.display()))?;
+        let (width, height) = decoder.dimensions();
+        let turned = matches!(
+            decoder.orientation()?,
+            Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH
+        );
+        Ok(if turned { ImageSize::new(height, width) } else { ImageSize::new(width, height) })
+    }
+}
+
+/// One encoding of a resized variant, for a `<source>` of a `<picture>` element
+#[derive(Clone, Serialize, Deserialize, TS, JsonSchema)]
+pub struct ImageSource {
+    /// `avif` or `webp`
+    pub format: String,
+    /// Value for the `type` attribute, e.g. `image/avif`
+    pub mime_type: String,
+    #[serde(flatten)]
+    #[ts(flatten)]
+    pub size: ImageSize,
+    pub url: String,
+}
+
+/// One preset in the source format
+#[derive(Clone, Serialize, Deserialize, TS, JsonSchema)]
+pub struct ImageVariant {
+    /// Name of the preset
+    pub name: String,
+    #[serde(flatten)]
+    #[ts(flatten)]
+    pub size: ImageSize,
+    pub url: String,
+}
+
+/// Shown in place of the cover until it has loaded
+#[derive(Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+pub struct ImagePlaceholder {
+    /// BlurHash of the cover, see https://blurha.sh
+    pub blurhash: String,
+    /// A few pixels wide PNG of the cover as a `data:` URL, to be scaled up and blurred
+    pub lqip: String,
+    /// Most common color of the cover as `#rrggbb`
+    pub color: String,
+}
+
+/// A placeholder in the cache, with the settings it was computed with
+#[derive(Serialize, Deserialize)]
+struct CachedPlaceholder {
+    blurhash_components: [u32; 2],
+    lqip_width: u32,
+    placeholder: ImagePlaceholder,
+}
+
+/// Files written for one source image and the settings they were written with, so a later
+/// build can delete those it no longer writes and write the others again when settings change
+#[derive(Serialize, Deserialize)]
+struct ImageOutputs {
+    /// `settings_hash` of the build
+    settings: String,
+    /// File names in the image's output directory
+    files: Vec<String>,
+}
+
+impl ImageOutputs {
+    /// The record of the last build. Without one the outputs were written before records were
+    /// kept, by builds whose presets were the fixed `-cover` and `-thumb` outputs.
+    fn load(names: &ImageNames, exts: &[&String]) -> Self {
+        fs::read_to_string(&names.record)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_else(|| ImageOutputs {
+                settings: String::new(),
+                files: LEGACY_SUFFIXES
+                    .iter()
+                    .flat_map(|suffix| exts.iter().map(move |ext| names.file_name(suffix, ext)))
+                    .collect(),
+            })
+    }
+
+    /// Delete the recorded outputs that are not in `files`
+    fn remove_superseded(&self, names: &ImageNames, files: &[String]) -> Result<()> {
+        for file in self.files.iter().filter(|file| !files.contains(file)) {
+            let path = names.dir.join(file);
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove superseded image: {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, names: &ImageNames) -> Result<()> {
+        if let Some(parent) = names.record.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+        }
+        fs::write(&names.record, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write image outputs: {}", names.record.display()))
+    }
+}
+
+/// Source metadata allowed by `images.keep_metadata`, written into every output that can hold it
+#[derive(Default)]
+struct KeptMetadata {
+    icc_profile: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    /// EXIF orientation of the source, `None` when it is upright
+    orientation: Option<Orientation>,
+}
+
+/// Output names of one source image: `<slug>-<name><suffix>.<ext>` in `images/<collection>/`
+struct ImageNames {
+    url_dir: String,
+    dir: PathBuf,
+    stem: String,
+    /// Extension of the source, used for the original and the fallback variants
+    ext: String,
+    /// `ImageOutputs` of the last build, in `<cache>/images/<collection>/`
+    record: PathBuf,
+}
+
+impl ImageNames {
+    fn new(config: &Config, collection: &str, slug: &str, filename: &str, source_path: &Path) -> Self {
+        let base_name = sanitize_filename(filename);
+        let ext = source_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("jpg");
+        let stem = format!("{slug}-{base_name}");
+        ImageNames {
+            url_dir: format!("/images/{collection}"),
+            dir: config.public_dir.join("images").join(collection),
+            record: config.cache.directory.join("images").join(collection).join(format!("{stem}.json")),
+            stem,
+            ext: ext.to_string(),
+        }
+    }
+
+    fn file_name(&self, suffix: &str, ext: &str) -> String {
+        format!("{}{suffix}.{ext}", self.stem)
+    }
+
+    fn path(&self, suffix: &str, ext: &str) -> PathBuf {
+        self.dir.join(self.file_name(suffix, ext))
+    }
+
+    fn url(&self, suffix: &str, ext: &str) -> String {
+        format!("{}/{}{suffix}.{ext}", self.url_dir, self.stem)
+    }
+}
+
+fn sanitize_filename(filename: &str) -> String {
+    // Remove the file extension if present
+    if let Some(stem) = Path::new(filename).file_stem() {
+        stem.to_string_lossy().to_string()
+    } else {
+        filename.to_string()
+    }
+}
+
+fn get_filter_type(filter_type: &str) -> FilterType {
+    match filter_type {
+        "Nearest" => FilterType::Nearest,
+        "Triangle" => FilterType::Triangle,
+        "CatmullRom" => FilterType::CatmullRom,
+        "Gaussian" => FilterType::Gaussian,
+        _ => FilterType::Lanczos3,
+    }
+}
+
+/// Hash of the image settings and encoders that outputs are written with
+pub fn settings_hash(config: &Config) -> Result<String> {
+    let images = &config.images;
+    let settings = serde_json::to_string(&(
+        ENCODING_VERSION,
+        &images.presets,
+        &images.filter_type,
+        &images.crop_focus,
+        &images.formats,
+        &images.quality,
+        &images.keep_metadata,
+    ))?;
+    Ok(format!("{:x}", Sha256::digest(settings)))
+}
+
+/// Where the `settings_hash` of the last build is kept
+fn settings_path(config: &Config) -> PathBuf {
+    config.cache.directory.join("images").join("settings.sha256")
+}
+
+/// Whether the last build wrote images with other settings than `hash`. Posts that are up to
+/// date still link those images, so every post has to be processed to write them again.
+pub fn settings_changed(config: &Config, hash: &str) -> bool {
+    fs::read_to_string(settings_path(config)).map_or(true, |last| last.trim() != hash)
+}
+
+pub fn record_settings(config: &Config, hash: &str) -> Result<()> {
+    let path = settings_path(config);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+    }
+    fs::write(&path, hash).with_context(|| format!("Failed to write image settings: {}", path.display()))
+}
+
+/// `jpg` and `jpeg` are the same format
+fn same_format(a: &str, b: &str) -> bool {
+    ImageFormat::from_extension(a).is_some_and(|format| Some(format) == ImageFormat::from_extension(b))
+}
+
+/// Configured encoder quality for a file extension
+fn quality(config: &Config, ext: &str) -> Option<u8> {
+    config
+        .images
+        .quality
+        .iter()
+        .find(|(format, _)| same_format(format, ext))
+        .map(|(_, quality)| (*quality).clamp(1, 100))
+}
+
+/// Parse a `cover_focus` of `x,y` fractions of the width and height, e.g. `0.3,0.6`
+pub fn parse_focus(value: &str) -> Result<(f32, f32)> {
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    let parse = |part: &str| part.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v));
+    match parts.as_slice() {
+        [x, y] => match (parse(x), parse(y)) {
+            (Some(x), Some(y)) => Ok((x, y)),
+            _ => anyhow::bail!("Expected two numbers between 0 and 1, like `0.3,0.6`, got `{value}`"),
+        },
+        _ => anyhow::bail!("Expected `x,y`, like `0.3,0.6`, got `{value}`"),
+    }
+}
+
+/// What a `cover` preset keeps when it crops
+#[derive(Clone, Copy)]
+enum Focus {
+    /// Fractions of the width and height to centre the crop on
+    Point(f32, f32),
+    Center,
+    Entropy,
+}
+
+/// Write the original and every preset of a post's cover image to `public/images/<collection>/`,
+/// each preset in the source format and every configured extra format. Only outputs older than
+/// the source or written with other settings are written again, and outputs of the last build
+/// that are no longer written are deleted. `cover` presets crop around `focus`, or as `images.crop_focus` says without one.
+/// URLs are fingerprinted when that is enabled.
+pub fn process_image(
+    source_path: &Path,
+    collection: &str,
+    slug: &str,
+    filename: &str,
+    focus: Option<(f32, f32)>,
+    config: &Config,
+    assets: &AssetManifest,
+) -> Result<Option<CoverImage>> {
+    let names = ImageNames::new(config, collection, slug, filename, source_path);
+    // Create image output directory in public folder, including any section subdirectories of the slug
+    let original_path = names.path("", &names.ext);
+    fs::create_dir_all(original_path.parent().unwrap_or(&names.dir))?;
+
+    let images = &config.images;
+    let formats: Vec<&String> = images
+        .formats
+        .iter()
+        .filter(|format| !same_format(format, &names.ext))
+        .collect();
+
+    let focus = match (focus, images.crop_focus) {
+        (Some((x, y)), _) => Focus::Point(x, y),
+        (None, CropFocus::Center) => Focus::Center,
+        (None, CropFocus::Entropy) => Focus::Entropy,
+    };
+    // Crops around a chosen point get their own file names, so moving the point replaces them
+    let suffix = |preset: &ImagePreset| match focus {
+        Focus::Point(x, y) if crops(preset) => {
+            format!("-{}-{}x{}", preset.name, (x * 100.0).round(), (y * 100.0).round())
+        }
+        _ => format!("-{}", preset.name),
+    };
+
+    let exts: Vec<&String> = std::iter::once(&names.ext).chain(formats.iter().copied()).collect();
+    let settings = settings_hash(config)?;
+    let previous = ImageOutputs::load(&names, &exts);
+    let stale = |path: &Path| -> Result<bool> {
+        Ok(previous.settings != settings || should_process_file(source_path, path, false)?)
+    };
+
+    // Outputs are written under their own names and moved to hashed names when fingerprinting is on
+    let published = |suffix: &str, ext: &str| assets.path(config, &names.url(suffix, ext));
+
+    // Stale outputs, per preset
+    let mut pending: Vec<(&ImagePreset, Vec<PathBuf>)> = Vec::new();
+    for preset in &images.presets {
+        let suffix = suffix(preset);
+        let mut outputs = Vec::new();
+        for ext in &exts {
+            if stale(&published(&suffix, ext))? {
+                outputs.push(names.path(&suffix, ext));
+            }
+        }
+        if !outputs.is_empty() {
+            pending.push((preset, outputs));
+        }
+    }
+
+    // Sizes of the images written now; the others are read from their files
+    let mut resized_sizes = Vec::new();
+    let mut original_size = None;
+    let original_pending = stale(&published("", &names.ext))?;
+    if original_pending || !pending.is_empty() {
+        let (img, metadata) = open_image(config, source_path)?;
+        if original_pending {
+            write_original(config, source_path, &original_path, &img, &metadata)?;
+        }
+        let filter_type = get_filter_type(&images.filter_type);
+        resized_sizes = pending
+            .par_iter()
+            .map(|(preset, outputs)| -> Result<(&str, ImageSize)> {
+                let resized = resize(&img, preset, focus, filter_type);
+                outputs
+                    .par_iter()
+                    .try_for_each(|path| write_image(config, &resized, path, &metadata))?;
+                Ok((preset.name.as_str(), ImageSize::new(resized.width(), resized.height())))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        original_size = Some(ImageSize::new(img.width(), img.height()));
+    }
+    let original_size = match original_size {
+        Some(size) => size,
+        None => ImageSize::read(&published("", &names.ext))?,
+    };
+
+    // Every encoding of a preset holds the same pixels as its fallback
+    let mut variants = Vec::new();
+    for preset in &images.presets {
+        let suffix = suffix(preset);
+        let size = match resized_sizes.iter().find(|(name, _)| *name == preset.name) {
+            Some((_, size)) => *size,
+            None => ImageSize::read(&published(&suffix, &names.ext))?,
+        };
+        variants.push((preset, suffix, size));
+    }
+    variants.sort_by_key(|(_, _, size)| size.width);
+
+    let mut sources = Vec::new();
+    for format in &formats {
+        for (_, suffix, size) in &variants {
+            sources.push(ImageSource {
+                format: format.to_string(),
+                mime_type: ImageFormat::from_extension(format.as_str())
+                    .map(|f| f.to_mime_type().to_string())
+                    .unwrap_or_else(|| format!("image/{format}")),
+                size: *size,
+                url: assets.fingerprint(config, &names.url(suffix, format))?,
+            });
+        }
+    }
+
+    let cover_suffix = variants
+        .iter()
+        .find(|(preset, ..)| preset.name == "cover")
+        .or(variants.last())
+        .map(|(_, suffix, ..)| suffix.clone());
+    let placeholder = match cover_suffix {
+        Some(suffix) if images.placeholders.enabled => Some(placeholder(config, &published(&suffix, &names.ext))?),
+        _ => None,
+    };
+
+    let variants = variants
+        .into_iter()
+        .map(|(preset, suffix, size)| -> Result<ImageVariant> {
+            Ok(ImageVariant {
+                name: preset.name.clone(),
+                size,
+                url: assets.fingerprint(config, &names.url(&suffix, &names.ext))?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let named = |name: &str| variants.iter().find(|v| v.name == name).map(|v| v.url.clone());
+    let (Some(narrowest), Some(widest)) = (variants.first(), variants.last()) else {
+        anyhow::bail!("No image presets configured");
+    };
+
+    let original = assets.fingerprint(config, &names.url("", &names.ext))?;
+
+    // Recorded under the names they are published under, so hashed files are deleted too
+    let url_prefix = format!("{}/", names.url_dir);
+    let mut files: Vec<String> = std::iter::once(&original)
+        .chain(variants.iter().map(|v| &v.url))
+        .chain(sources.iter().map(|s| &s.url))
+        .filter_map(|url| url.strip_prefix(&url_prefix))
+        .map(str::to_string)
+        .collect();
+    files.sort();
+    files.dedup();
+    previous.remove_superseded(&names, &files)?;
+    ImageOutputs { settings, files }.write(&names)?;
+
+    Ok(Some(CoverImage {
+        original,
+        original_size,
+        cover: named("cover").unwrap_or_else(|| widest.url.clone()),
+        thumbnail: named("thumbnail").unwrap_or_else(|| narrowest.url.clone()),
+        srcset: srcset(variants.iter().map(|v| (v.url.as_str(), v.size.width))),
+        variants,
+        sources,
+        placeholder,
+    }))
+}
+
+/// Placeholder of a cover variant, from the cache unless the variant was written since
+fn placeholder(config: &Config, cover_path: &Path) -> Result<ImagePlaceholder> {
+    let settings = &config.images.placeholders;
+    let relative = cover_path.strip_prefix(&config.public_dir).unwrap_or(cover_path);
+    let cache_path = config
+        .cache
+        .directory
+        .join("placeholders")
+        .join(relative)
+        .with_extension("json");
+
+    if config.cache.enabled && !should_process_file(cover_path, &cache_path, false)? {
+        let cached = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<CachedPlaceholder>(&json).ok())
+            .filter(|cached| {
+                cached.blurhash_components == settings.blurhash_components && cached.lqip_width == settings.lqip_width
+            });
+        if let Some(cached) = cached {
+            return Ok(cached.placeholder);
+        }
+    }
+
+    let img = image::open(cover_path)
+        .with_context(|| format!("Failed to open image for placeholder: {}", cover_path.display()))?;
+    let sample = img.thumbnail(PLACEHOLDER_SAMPLE_SIZE, PLACEHOLDER_SAMPLE_SIZE).to_rgb8();
+    let [components_x, components_y] = settings.blurhash_components;
+
+    let mut lqip = Vec::new();
+    DynamicImage::ImageRgb8(img.thumbnail(settings.lqip_width.max(1), u32::MAX).to_rgb8())
+        .write_to(&mut Cursor::new(&mut lqip), ImageFormat::Png)
+        .context("Failed to encode LQIP image")?;
+
+    let placeholder = ImagePlaceholder {
+        blurhash: blurhash::encode(&sample, components_x, components_y),
+        lqip: format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(lqip)),
+        color: dominant_color(&sample),
+    };
+
+    if config.cache.enabled {
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+        }
+        let cached = CachedPlaceholder {
+            blurhash_components: settings.blurhash_components,
+            lqip_width: settings.lqip_width,
+            placeholder: placeholder.clone(),
+        };
+        fs::write(&cache_path, serde_json::to_string(&cached)?)
+            .with_context(|| format!("Failed to write placeholder cache: {}", cache_path.display()))?;
+    }
+    Ok(placeholder)
+}
+
+/// Average of the most populated bucket when every channel is reduced to 16 levels
+fn dominant_color(img: &RgbImage) -> String {
+    let mut buckets = vec![(0u32, [0u32; 3]); 16 * 16 * 16];
+    for pixel in img.pixels() {
+        let [r, g, b] = pixel.0;
+        let bucket = &mut buckets[(usize::from(r >> 4) << 8) | (usize::from(g >> 4) << 4) | usize::from(b >> 4)];
+        bucket.0 += 1;
+        for (sum, value) in bucket.1.iter_mut().zip(pixel.0) {
+            *sum += u32::from(value);
+        }
+    }
+    let (count, sums) = buckets
+        .into_iter()
+        .max_by_key(|(count, _)| *count)
+        .unwrap_or_default();
+    let [r, g, b] = sums.map(|sum| sum.checked_div(count).unwrap_or(0));
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// `url 400w, url 1200w`, keeping the first of several variants with the same width
+pub fn srcset<'a>(candidates: impl IntoIterator<Item = (&'a str, u32)>) -> String {
+    let mut widths = Vec::new();
+    let mut srcset = Vec::new();
+    for (url, width) in candidates {
+        if !widths.contains(&width) {
+            widths.push(width);
+            srcset.push(format!("{url} {width}w"));
+        }
+    }
+    srcset.join(", ")
+}
+
+/// A `cover` preset without a height has nothing to crop to and fits like `contain`
+fn crops(preset: &ImagePreset) -> bool {
+    preset.fit == FitMode::Cover && preset.height.is_some()
+}
+
+fn resize(img: &DynamicImage, preset: &ImagePreset, focus: Focus, filter_type: FilterType) -> DynamicImage {
+    let height = preset.height.unwrap_or(u32::MAX);
+    match preset.fit {
+        FitMode::Cover if crops(preset) => {
+            let (x, y, width, crop_height) = crop_window(img, preset.width, height, focus);
+            img.crop_imm(x, y, width, crop_height)
+                .resize_exact(preset.width, height, filter_type)
+        }
+        FitMode::ScaleDown if img.width() <= preset.width && img.height() <= height => img.clone(),
+        _ => img.resize(preset.width, height, filter_type),
+    }
+}
+
+/// The largest `x, y, width, height` window of `img` with the aspect ratio of `width` × `height`
+fn crop_window(img: &DynamicImage, width: u32, height: u32, focus: Focus) -> (u32, u32, u32, u32) {
+    let (img_width, img_height) = img.dimensions();
+    let (crop_width, crop_height) = if u64::from(img_width) * u64::from(height) > u64::from(img_height) * u64::from(width) {
+        let crop_width = (f64::from(img_height) * f64::from(width) / f64::from(height)).round() as u32;
+        (crop_width.clamp(1, img_width), img_height)
+    } else {
+        let crop_height = (f64::from(img_width) * f64::from(height) / f64::from(width)).round() as u32;
+        (img_width, crop_height.clamp(1, img_height))
+    };
+    let (max_x, max_y) = (img_width - crop_width, img_height - crop_height);
+
+    let centred = |fraction: f32, length: u32, crop: u32, max: u32| {
+        ((f64::from(fraction) * f64::from(length) - f64::from(crop) / 2.0).round().max(0.0) as u32).min(max)
+    };
+    let (x, y) = match focus {
+        Focus::Point(fx, fy) => (
+            centred(fx, img_width, crop_width, max_x),
+            centred(fy, img_height, crop_height, max_y),
+        ),
+        Focus::Center => (max_x / 2, max_y / 2),
+        Focus::Entropy => entropy_offset(img, crop_width, crop_height),
+    };
+    (x, y, crop_width, crop_height)
+}
+
+/// Offset of the `crop_width` × `crop_height` window with the most varied grey levels,
+/// judged on a downscaled copy of the image
+fn entropy_offset(img: &DynamicImage, crop_width: u32, crop_height: u32) -> (u32, u32) {
+    let (img_width, img_height) = img.dimensions();
+    let sample = img.thumbnail(ENTROPY_SAMPLE_SIZE, ENTROPY_SAMPLE_SIZE).to_luma8();
+    let scale_x = f64::from(sample.width()) / f64::from(img_width);
+    let scale_y = f64::from(sample.height()) / f64::from(img_height);
+    let window_width = ((f64::from(crop_width) * scale_x).round() as u32).clamp(1, sample.width());
+    let window_height = ((f64::from(crop_height) * scale_y).round() as u32).clamp(1, sample.height());
+
+    let (max_x, max_y) = (sample.width() - window_width, sample.height() - window_height);
+    let step = |max: u32| (max / ENTROPY_CANDIDATES).max(1) as usize;
+    let mut best = (0, 0, f64::MIN);
+    for y in (0..=max_y).step_by(step(max_y)) {
+        for x in (0..=max_x).step_by(step(max_x)) {
+            let score = entropy(&sample, x, y, window_width, window_height);
+            if score > best.2 {
+                best = (x, y, score);
+            }
+        }
+    }
+
+    let (x, y, _) = best;
+    (
+        ((f64::from(x) / scale_x).round() as u32).min(img_width - crop_width),
+        ((f64::from(y) / scale_y).round() as u32).min(img_height - crop_height),
+    )
+}
+
+/// Shannon entropy of the grey levels in a window, in bits
+fn entropy(img: &GrayImage, x: u32, y: u32, width: u32, height: u32) -> f64 {
+    let mut histogram = [0u32; 256];
+    for row in y..y + height {
+        for column in x..x + width {
+            histogram[usize::from(img.get_pixel(column, row).0[0])] += 1;
+        }
+    }
+    let total = f64::from(width * height);
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = f64::from(count) / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Decode an image upright, as its EXIF orientation says, with the metadata that is kept
+fn open_image(config: &Config, path: &Path) -> Result<(DynamicImage, KeptMetadata)> {
+    let mut decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .with_context(|| format!("Failed to open image for processing: {}", path.display()))?
+        .into_decoder()
+        .with_context(|| format!("Failed to read image: {}", path.display()))?;
+
+    let keep = &config.images.keep_metadata;
+    let orientation = decoder.orientation()?;
+    let icc_profile = if keep.contains(&ImageMetadata::Icc) {
+        decoder.icc_profile()?
+    } else {
+        None
+    };
+    let exif = if keep.contains(&ImageMetadata::Copyright) {
+        decoder.exif_metadata()?.and_then(|exif| copyright_exif(&exif))
+    } else {
+        None
+    };
+    let metadata = KeptMetadata {
+        icc_profile,
+        exif,
+        orientation: (orientation != Orientation::NoTransforms).then_some(orientation),
+    };
+
+    let mut img = DynamicImage::from_decoder(decoder)
+        .with_context(|| format!("Failed to decode image: {}", path.display()))?;
+    img.apply_orientation(orientation);
+    Ok((img, metadata))
+}
+
+/// An EXIF entry: tag, field type, count and the value in little-endian order
+type ExifEntry<'a> = (u16, u16, u32, Cow<'a, [u8]>);
+
+/// The Artist and Copyright entries of a TIFF-structured EXIF block
+fn copyright_entries(exif: &[u8]) -> Option<Vec<ExifEntry<'_>>> {
+    let little_endian = match exif.get(..4)? {
+        b"II*\0" => true,
+        b"MM\0*" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = exif.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<usize> {
+        let bytes = exif.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) } as usize)
+    };
+
+    let ifd = u32_at(4)?;
+    let mut kept = Vec::new();
+    for index in 0..usize::from(u16_at(ifd)?) {
+        let entry = ifd + 2 + index * 12;
+        let tag = u16_at(entry)?;
+        if !COPYRIGHT_TAGS.contains(&tag) || u16_at(entry + 2)? != EXIF_ASCII {
+            continue;
+        }
+        let length = u32_at(entry + 4)?;
+        let start = if length <= 4 { entry + 8 } else { u32_at(entry + 8)? };
+        kept.push((tag, EXIF_ASCII, length as u32, Cow::Borrowed(exif.get(start..start + length)?)));
+    }
+    Some(kept)
+}
+
+/// A little-endian EXIF block with one IFD of `entries`, `None` without any
+fn exif_block(mut entries: Vec<ExifEntry>) -> Option<Vec<u8>> {
+    if entries.is_empty() {
+        return None;
+    }
+    entries.sort_by_key(|(tag, ..)| *tag);
+
+    // Little-endian TIFF header, one IFD with the entries, then the values that do not fit in one
+    let mut out = b"II*\0".to_vec();
+    out.extend(8u32.to_le_bytes());
+    out.extend((entries.len() as u16).to_le_bytes());
+    let values_offset = 8 + 2 + entries.len() * 12 + 4;
+    let mut values = Vec::new();
+    for (tag, kind, count, value) in &entries {
+        out.extend(tag.to_le_bytes());
+        out.extend(kind.to_le_bytes());
+        out.extend(count.to_le_bytes());
+        if value.len() <= 4 {
+            let mut inline = value.to_vec();
+            inline.resize(4, 0);
+            out.extend(inline);
+        } else {
+            out.extend(((values_offset + values.len()) as u32).to_le_bytes());
+            values.extend_from_slice(value);
+            // Values start on word boundaries
+            if values.len() % 2 == 1 {
+                values.push(0);
+            }
+        }
+    }
+    out.extend(0u32.to_le_bytes());
+    out.extend(values);
+    Some(out)
+}
+
+/// A new EXIF block with only the Artist and Copyright entries of a TIFF-structured EXIF block
+fn copyright_exif(exif: &[u8]) -> Option<Vec<u8>> {
+    exif_block(copyright_entries(exif)?)
+}
+
+/// Hand the kept metadata to an encoder; formats that cannot hold a kind of it leave it out
+fn with_metadata<E: ImageEncoder>(mut encoder: E, metadata: &KeptMetadata) -> E {
+    if let Some(icc_profile) = &metadata.icc_profile {
+        let _ = encoder.set_icc_profile(icc_profile.clone());
+    }
+    if let Some(exif) = &metadata.exif {
+        let _ = encoder.set_exif_metadata(exif.clone());
+    }
+    encoder
+}
+
+/// Encode an image in the format of its file extension with the configured quality. Nothing
+/// but the kept metadata is written.
+fn write_image(config: &Config, img: &DynamicImage, output_path: &Path, metadata: &KeptMetadata) -> Result<()> {
+    let ext = output_path.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+    let format = ImageFormat::from_extension(ext).unwrap_or(ImageFormat::Jpeg);
+
+    let output_file = File::create(output_path)
+        .with_context(|| format!("Failed to create image output file: {}", output_path.display()))?;
+    let mut writer = BufWriter::new(output_file);
+
+    let written = match format {
+        ImageFormat::Avif => {
+            let encoder = AvifEncoder::new_with_speed_quality(
+                &mut writer,
+                AVIF_SPEED,
+                quality(config, ext).unwrap_or(DEFAULT_AVIF_QUALITY),
+            );
+            let encoder = with_metadata(encoder, metadata);
+            match img.color() {
+                ColorType::Rgb8 | ColorType::Rgba8 => img.write_with_encoder(encoder),
+                _ => DynamicImage::ImageRgba8(img.to_rgba8()).write_with_encoder(encoder),
+            }
+        }
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let encoder = JpegEncoder::new_with_quality(&mut writer, quality(config, ext).unwrap_or(DEFAULT_JPEG_QUALITY));
+            DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(with_metadata(encoder, metadata))
+        }
+        ImageFormat::Png => img.write_with_encoder(with_metadata(PngEncoder::new(&mut writer), metadata)),
+        ImageFormat::WebP => {
+            let quality = quality(config, ext).unwrap_or(DEFAULT_WEBP_QUALITY);
+            let encoded = if img.color().has_alpha() {
+                let rgba = img.to_rgba8();
+                webp::Encoder::from_rgba(&rgba, img.width(), img.height()).encode_simple(false, f32::from(quality))
+            } else {
+                let rgb = img.to_rgb8();
+                webp::Encoder::from_rgb(&rgb, img.width(), img.height()).encode_simple(false, f32::from(quality))
+            };
+            let encoded = encoded
+                .map_err(|e| anyhow::anyhow!("WebP encoding failed: {e:?}"))
+                .with_context(|| format!("Failed to write processed image to: {}", output_path.display()))?;
+            let data = webp_with_metadata(&encoded, img.width(), img.height(), metadata)
+                .with_context(|| format!("Failed to add metadata to WebP image: {}", output_path.display()))?;
+            writer.write_all(&data).map_err(image::ImageError::IoError)
+        }
+        _ => img.write_to(&mut writer, format),
+    };
+    written.with_context(|| format!("Failed to write processed image to: {}", output_path.display()))
+}
+
+/// Copy the source to the original output without the metadata that is not kept, so its pixels
+/// are not encoded again. JPEG originals keep their EXIF orientation, which browsers apply; PNG
+/// and WebP sources that are not upright, other formats and files that do not parse are encoded
+/// again from `img`.
+fn write_original(
+    config: &Config,
+    source_path: &Path,
+    output_path: &Path,
+    img: &DynamicImage,
+    metadata: &KeptMetadata,
+) -> Result<()> {
+    let data = fs::read(source_path)
+        .with_context(|| format!("Failed to read image: {}", source_path.display()))?;
+    let keep_icc = metadata.icc_profile.is_some();
+    let stripped = match image::guess_format(&data).ok() {
+        Some(ImageFormat::Jpeg) => {
+            let mut entries = metadata
+                .exif
+                .as_deref()
+                .and_then(copyright_entries)
+                .unwrap_or_default();
+            if let Some(orientation) = metadata.orientation {
+                let value = u16::from(orientation.to_exif()).to_le_bytes();
+                entries.push((ORIENTATION_TAG, EXIF_SHORT, 1, Cow::Owned(value.to_vec())));
+            }
+            jpeg_without_metadata(&data, exif_block(entries).as_deref(), keep_icc)
+        }
+        Some(ImageFormat::Png) if metadata.orientation.is_none() => {
+            png_without_metadata(&data, metadata.exif.as_deref(), keep_icc)
+        }
+        Some(ImageFormat::WebP) if metadata.orientation.is_none() => {
+            webp_without_metadata(&data, img.width(), img.height(), metadata)
+        }
+        _ => None,
+    };
+    match stripped {
+        Some(stripped) => fs::write(output_path, stripped)
+            .with_context(|| format!("Failed to write processed image to: {}", output_path.display())),
+        None => write_image(config, img, output_path, metadata),
+    }
+}
+
+/// A JPEG file without its APP segments and comments but the JFIF and Adobe headers, the ICC
+/// profile when `keep_icc` and a new EXIF segment of `exif`
+fn jpeg_without_metadata(data: &[u8], mut exif: Option<&[u8]>, keep_icc: bool) -> Option<Vec<u8>> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut out = vec![0xFF, 0xD8];
+    let mut offset = 2;
+    loop {
+        if *data.get(offset)? != 0xFF {
+            return None;
+        }
+        // Markers may be padded with extra 0xFF bytes
+        while *data.get(offset + 1)? == 0xFF {
+            offset += 1;
+        }
+        let marker = data[offset + 1];
+        // EXIF follows the JFIF header, which has to come first
+        if marker != 0xE0 {
+            if let Some(exif) = exif.take() {
+                out.extend([0xFF, 0xE1]);
+                out.extend(u16::try_from(2 + 6 + exif.len()).ok()?.to_be_bytes());
+                out.extend(b"Exif\0\0");
+                out.extend(exif);
+            }
+        }
+        // The entropy-coded image data follows the start of scan, without any metadata in it
+        if marker == 0xDA {
+            out.extend(&data[offset..]);
+            return Some(out);
+        }
+
+        let length = usize::from(u16::from_be_bytes(data.get(offset + 2..offset + 4)?.try_into().ok()?));
+        let segment = data.get(offset..offset + 2 + length)?;
+        let keep = match marker {
+            0xE0 | 0xEE => true,
+            0xE2 => keep_icc && segment.get(4..16) == Some(b"ICC_PROFILE\0".as_slice()),
+            0xE1..=0xEF | 0xFE => false,
+            _ => true,
+        };
+        if keep {
+            out.extend(segment);
+        }
+        offset += 2 + length;
+    }
+}
+
+/// A PNG file without its text, time and EXIF chunks, without the ICC profile unless `keep_icc`,
+/// and with a new `eXIf` chunk of `exif`
+fn png_without_metadata(data: &[u8], mut exif: Option<&[u8]>, keep_icc: bool) -> Option<Vec<u8>> {
+    if data.get(..8)? != PNG_SIGNATURE {
+        return None;
+    }
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut offset = 8;
+    while offset < data.len() {
+        let length = u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let kind: [u8; 4] = data.get(offset + 4..offset + 8)?.try_into().ok()?;
+        let chunk = data.get(offset..offset + 12 + length)?;
+        // `eXIf` has to come before the image data
+        if &kind == b"IDAT" {
+            if let Some(exif) = exif.take() {
+                out.extend(png_chunk(b"eXIf", exif));
+            }
+        }
+        let keep = match &kind {
+            b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" => false,
+            b"iCCP" => keep_icc,
+            _ => true,
+        };
+        if keep {
+            out.extend(chunk);
+        }
+        offset += 12 + length;
+    }
+    Some(out)
+}
+
+/// A PNG chunk with its length and checksum
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend(kind);
+    chunk.extend(data);
+    chunk.extend(hasher.finalize().to_be_bytes());
+    chunk
+}
+
+/// A WebP file without its ICC profile, EXIF and XMP chunks, with the kept metadata instead
+fn webp_without_metadata(data: &[u8], width: u32, height: u32, metadata: &KeptMetadata) -> Option<Vec<u8>> {
+    let chunks = webp_chunks(data)?;
+    let header = chunks
+        .first()
+        .filter(|(fourcc, _)| fourcc == b"VP8X")
+        .map(|(_, header)| {
+            let mut header = header.to_vec();
+            if let Some(flags) = header.first_mut() {
+                *flags &= !(WEBP_ICC_FLAG | WEBP_EXIF_FLAG | WEBP_XMP_FLAG);
+            }
+            header
+        });
+    let mut stripped: Vec<([u8; 4], &[u8])> = chunks
+        .into_iter()
+        .filter(|(fourcc, _)| !matches!(fourcc, b"VP8X" | b"ICCP" | b"EXIF" | b"XMP "))
+        .collect();
+    if let Some(header) = &header {
+        stripped.insert(0, (*b"VP8X", header.as_slice()));
+    }
+    webp_with_metadata(&webp_file(&stripped), width, height, metadata).ok()
+}
+
+/// Chunks of a RIFF container with their four-character codes, `None` if it is not a WebP file
+fn webp_chunks(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset < data.len() {
+        let fourcc: [u8; 4] = data.get(offset..offset + 4)?.try_into().ok()?;
+        let size = u32::from_le_bytes(data.get(offset + 4..offset + 8)?.try_into().ok()?) as usize;
+        chunks.push((fourcc, data.get(offset + 8..offset + 8 + size)?));
+        // Chunks are padded to an even size
+        offset += 8 + size + size % 2;
+    }
+    Some(chunks)
+}
+
+/// A WebP file from its chunks
+fn webp_file(chunks: &[([u8; 4], &[u8])]) -> Vec<u8> {
+    let mut body = b"WEBP".to_vec();
+    for (fourcc, data) in chunks {
+        body.extend(fourcc);
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend(*data);
+        if data.len() % 2 == 1 {
+            body.push(0);
+        }
+    }
+    let mut file = b"RIFF".to_vec();
+    file.extend((body.len() as u32).to_le_bytes());
+    file.extend(body);
+    file
+}
+
+/// Add the kept ICC profile and EXIF block to an encoded WebP image. They need the extended
+/// format, whose `VP8X` header is added when the encoder wrote the simple one.
+fn webp_with_metadata(encoded: &[u8], width: u32, height: u32, metadata: &KeptMetadata) -> Result<Vec<u8>> {
+    if metadata.icc_profile.is_none() && metadata.exif.is_none() {
+        return Ok(encoded.to_vec());
+    }
+    anyhow::ensure!(width > 0 && height > 0, "Cannot write metadata into an empty {width}×{height} WebP image");
+    let chunks = webp_chunks(encoded).context("Encoder output is not a WebP file")?;
+
+    let mut header = match chunks.first().filter(|(fourcc, _)| fourcc == b"VP8X") {
+        Some((_, data)) => data.to_vec(),
+        None => {
+            // Flags, three reserved bytes and the canvas size minus one in 24 bits each
+            let mut header = vec![0; 4];
+            header.extend(&(width - 1).to_le_bytes()[..3]);
+            header.extend(&(height - 1).to_le_bytes()[..3]);
+            header
+        }
+    };
+    let mut extended: Vec<([u8; 4], &[u8])> = Vec::new();
+    if let Some(icc_profile) = &metadata.icc_profile {
+        header[0] |= WEBP_ICC_FLAG;
+        extended.push((*b"ICCP", icc_profile.as_slice()));
+    }
+    extended.extend(chunks.into_iter().filter(|(fourcc, _)| fourcc != b"VP8X"));
+    if let Some(exif) = &metadata.exif {
+        header[0] |= WEBP_EXIF_FLAG;
+        extended.push((*b"EXIF", exif.as_slice()));
+    }
+    extended.insert(0, (*b"VP8X", header.as_slice()));
+    Ok(webp_file(&extended))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::metadata::Orientation;
+
+    #[test]
+    fn test_png_cover_gets_presets_and_extra_formats() {
+        let dir = std::env::temp_dir().join(format!("site-builder-images-{}", std::process::id()));
+        let source = dir.join("content/photo.png");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([x as u8 * 4, y as u8 * 5, 128])))
+            .save(&source)
+            .unwrap();
+
+        let mut config = Config {
+            public_dir: dir.join("public"),
+            ..Default::default()
+        };
+        config.cache.directory = dir.join("cache");
+        let preset = |name: &str, width: u32, fit: FitMode| ImagePreset {
+            name: name.to_string(),
+            width,
+            height: None,
+            fit,
+        };
+        config.images.presets = vec![
+            preset("cover", 32, FitMode::Contain),
+            preset("thumbnail", 16, FitMode::Contain),
+            preset("w128", 128, FitMode::ScaleDown),
+        ];
+        config.images.formats = vec!["avif".to_string(), "webp".to_string()];
+        let cover = process_image(&source, "blog", "hello", "photo.png", None, &config, &AssetManifest::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(cover.cover, "/images/blog/hello-photo-cover.png");
+        assert_eq!(cover.thumbnail, "/images/blog/hello-photo-thumbnail.png");
+        let variants: Vec<(&str, u32, u32)> = cover.variants.iter().map(|v| (v.name.as_str(), v.size.width, v.size.height)).collect();
+        assert_eq!(variants, [("thumbnail", 16, 12), ("cover", 32, 24), ("w128", 64, 48)]);
+        assert_eq!(cover.original_size, ImageSize::new(64, 48));
+        assert_eq!(cover.variants[0].size.aspect_ratio, 1.3333);
+
+        // Nothing is written again, so the sizes come from the existing files
+        let again = process_image(&source, "blog", "hello", "photo.png", None, &config, &AssetManifest::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(again.original_size, cover.original_size);
+        assert!(again.variants.iter().zip(&cover.variants).all(|(a, b)| a.size == b.size));
+        assert_eq!(
+            cover.srcset,
+            "/images/blog/hello-photo-thumbnail.png 16w, /images/blog/hello-photo-cover.png 32w, /images/blog/hello-photo-w128.png 64w"
+        );
+        let sources: Vec<(&str, u32, &str)> = cover
+            .sources
+            .iter()
+            .filter(|s| s.size.width < 64)
+            .map(|s| (s.format.as_str(), s.size.width, s.url.as_str()))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("avif", 16, "/images/blog/hello-photo-thumbnail.avif"),
+                ("avif", 32, "/images/blog/hello-photo-cover.avif"),
+                ("webp", 16, "/images/blog/hello-photo-thumbnail.webp"),
+                ("webp", 32, "/images/blog/hello-photo-cover.webp"),
+            ]
+        );
+        assert_eq!(cover.sources[0].mime_type, "image/avif");
+        for source in &cover.sources {
+            assert!(config.public_dir.join(source.url.trim_start_matches('/')).exists());
+        }
+        let placeholder = cover.placeholder.as_ref().unwrap();
+        assert_eq!(placeholder.blurhash.len(), 1 + 1 + 4 + 2 * 11);
+        assert!(placeholder.lqip.starts_with("data:image/png;base64,"));
+        assert_eq!(placeholder.color.len(), 7);
+        assert!(dir.join("cache/placeholders/images/blog/hello-photo-cover.json").exists());
+        assert_eq!(dominant_color(&RgbImage::from_pixel(4, 4, image::Rgb([10, 20, 30]))), "#0a141e");
+        assert!(same_format("jpg", "JPEG"));
+        assert_eq!(quality(&config, "jpg"), Some(82));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_superseded_outputs_are_removed() {
+        let dir = std::env::temp_dir().join(format!("site-builder-superseded-{}", std::process::id()));
+        let source = dir.join("content/photo.png");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 30, image::Rgb([90, 120, 150])))
+            .save(&source)
+            .unwrap();
+
+        let mut config = Config {
+            public_dir: dir.join("public"),
+            ..Default::default()
+        };
+        config.cache.directory = dir.join("cache");
+        config.images.formats = vec!["webp".to_string()];
+        config.images.placeholders.enabled = false;
+        for preset in &mut config.images.presets {
+            preset.fit = FitMode::Cover;
+        }
+        let output = |name: &str| config.public_dir.join("images/blog").join(name);
+
+        // Outputs of a build before presets, and of another image whose name starts the same
+        fs::create_dir_all(output("")).unwrap();
+        for name in ["hello-photo-thumb.png", "hello-photo-thumb.webp", "hello-photo-2.png"] {
+            fs::write(output(name), b"old").unwrap();
+        }
+        process_image(&source, "blog", "hello", "photo.png", None, &config, &AssetManifest::default()).unwrap();
+        assert!(!output("hello-photo-thumb.png").exists());
+        assert!(!output("hello-photo-thumb.webp").exists());
+        assert!(output("hello-photo-2.png").exists());
+        assert!(output("hello-photo-thumbnail.webp").exists());
+
+        // Moving the crop point and renaming a preset replace their files
+        config.images.presets[0].name = "small".to_string();
+        process_image(&source, "blog", "hello", "photo.png", Some((0.25, 0.5)), &config, &AssetManifest::default()).unwrap();
+        assert!(!output("hello-photo-thumbnail.png").exists());
+        assert!(!output("hello-photo-cover.webp").exists());
+        assert!(output("hello-photo-small-25x50.png").exists());
+        assert!(output("hello-photo-cover-25x50.webp").exists());
+        assert!(output("hello-photo.png").exists());
+
+        // Fingerprinted outputs are only published under their hashed names, are found again by
+        // the next build, and replace the hashed files of the last build when the source changes
+        config.assets.fingerprint = true;
+        let assets = AssetManifest::default();
+        let published = |cover: &CoverImage| -> Vec<String> {
+            let mut urls: Vec<String> = cover.variants.iter().map(|v| v.url.clone()).collect();
+            urls.extend(cover.sources.iter().map(|s| s.url.clone()));
+            urls.push(cover.original.clone());
+            urls
+        };
+        let first = process_image(&source, "blog", "hello", "photo.png", None, &config, &assets).unwrap().unwrap();
+        assert!(!output("hello-photo.png").exists());
+        assert!(!output("hello-photo-cover.webp").exists());
+        assert!(published(&first).iter().all(|url| config.public_dir.join(url.trim_start_matches('/')).exists()));
+        let again = process_image(&source, "blog", "hello", "photo.png", None, &config, &assets).unwrap().unwrap();
+        assert_eq!(published(&again), published(&first));
+
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 30, image::Rgb([10, 20, 30])))
+            .save(&source)
+            .unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options().write(true).open(&source).unwrap().set_modified(later).unwrap();
+        let changed = process_image(&source, "blog", "hello", "photo.png", None, &config, &assets).unwrap().unwrap();
+        assert_ne!(changed.original, first.original);
+        assert!(published(&first).iter().all(|url| !config.public_dir.join(url.trim_start_matches('/')).exists()));
+        assert_eq!(fs::read_dir(output("")).unwrap().count(), published(&changed).len() + 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_sizes_fill_the_presets() {
+        let yaml = "cover_width: 1600\nthumbnail_width: 300\nthumbnail_height: 200\npresets:\n  - name: cover\n    width: 1200\n    height: 800\n    fit: cover\n";
+        let mut settings: crate::config::ImageSettings = serde_yaml::from_str(yaml).unwrap();
+        let warnings = settings.apply_legacy_sizes();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("`images.cover_width`"));
+        let presets: Vec<(&str, u32, Option<u32>, FitMode)> = settings
+            .presets
+            .iter()
+            .map(|preset| (preset.name.as_str(), preset.width, preset.height, preset.fit))
+            .collect();
+        // The removed keys scaled to fit, so a cropping preset they size stops cropping
+        assert_eq!(
+            presets,
+            [("cover", 1600, Some(800), FitMode::Contain), ("thumbnail", 300, Some(200), FitMode::Contain)]
+        );
+        assert!(settings.apply_legacy_sizes().is_empty());
+        assert!(!serde_yaml::to_string(&settings).unwrap().contains("cover_width"));
+    }
+
+    #[test]
+    fn test_orientation_is_applied_and_metadata_stripped() {
+        let dir = std::env::temp_dir().join(format!("site-builder-exif-{}", std::process::id()));
+        let source = dir.join("content/phone.jpg");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+
+        // Rotated 90° clockwise, with an artist, a copyright notice and a GPS block
+        let copyright = b"(c) Fjord Salmon AS\0";
+        let mut exif = b"II*\0".to_vec();
+        exif.extend(8u32.to_le_bytes());
+        exif.extend(4u16.to_le_bytes());
+        let entry = |tag: u16, kind: u16, count: u32, value: u32| {
+            [tag.to_le_bytes().to_vec(), kind.to_le_bytes().to_vec(), count.to_le_bytes().to_vec(), value.to_le_bytes().to_vec()].concat()
+        };
+        exif.extend(entry(0x0112, 3, 1, 6));
+        exif.extend(entry(0x013B, EXIF_ASCII, 4, u32::from_le_bytes(*b"Ada\0")));
+        exif.extend(entry(0x8298, EXIF_ASCII, copyright.len() as u32, 62));
+        exif.extend(entry(0x8825, 4, 1, 62));
+        exif.extend(0u32.to_le_bytes());
+        exif.extend(copyright);
+
+        let file = File::create(&source).unwrap();
+        let mut encoder = JpegEncoder::new(file);
+        encoder.set_exif_metadata(exif).unwrap();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 20, image::Rgb([200, 80, 40])))
+            .write_with_encoder(encoder)
+            .unwrap();
+
+        let mut config = Config {
+            public_dir: dir.join("public"),
+            ..Default::default()
+        };
+        config.cache.directory = dir.join("cache");
+        config.images.formats = Vec::new();
+        config.images.presets = vec![ImagePreset {
+            name: "cover".to_string(),
+            width: 10,
+            height: None,
+            fit: FitMode::Contain,
+        }];
+        let exif_of = |url: &str| {
+            let path = config.public_dir.join(url.trim_start_matches('/'));
+            ImageReader::open(path).unwrap().into_decoder().unwrap().exif_metadata().unwrap()
+        };
+        // Scan data of a JPEG file, from its start of scan marker on
+        let scan = |data: &[u8]| data[data.windows(2).position(|marker| marker == [0xFF, 0xDA]).unwrap()..].to_vec();
+
+        // Builds before output records copied the source as it was
+        let original_path = config.public_dir.join("images/blog/visit-phone.jpg");
+        fs::create_dir_all(original_path.parent().unwrap()).unwrap();
+        fs::copy(&source, &original_path).unwrap();
+
+        let cover = process_image(&source, "blog", "visit", "phone.jpg", None, &config, &AssetManifest::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cover.original_size, ImageSize::new(20, 40));
+        assert_eq!(cover.variants[0].size, ImageSize::new(10, 20));
+        // The original keeps its pixels and only the orientation of its EXIF block
+        let original = exif_of(&cover.original).unwrap();
+        assert_eq!(Orientation::from_exif_chunk(&original), Some(Orientation::Rotate90));
+        assert_eq!(original.len(), 8 + 2 + 12 + 4);
+        assert_eq!(scan(&fs::read(&original_path).unwrap()), scan(&fs::read(&source).unwrap()));
+        assert_eq!(exif_of(&cover.cover), None);
+
+        // Changed settings write the outputs again, and the sizes of the existing files are read upright
+        config.images.keep_metadata = vec![ImageMetadata::Copyright];
+        let cover = process_image(&source, "blog", "visit", "phone.jpg", None, &config, &AssetManifest::default())
+            .unwrap()
+            .unwrap();
+        let kept = exif_of(&cover.cover).unwrap();
+        assert_eq!(Orientation::from_exif_chunk(&kept), None);
+        assert_eq!(copyright_exif(&kept), Some(kept.clone()));
+        assert!(kept.windows(copyright.len()).any(|window| window == copyright));
+        assert!(kept.windows(4).any(|window| window == b"Ada\0"));
+        assert_eq!(kept.len(), 8 + 2 + 2 * 12 + 4 + copyright.len());
+        let original = exif_of(&cover.original).unwrap();
+        assert_eq!(Orientation::from_exif_chunk(&original), Some(Orientation::Rotate90));
+        assert_eq!(original.len(), 8 + 2 + 3 * 12 + 4 + copyright.len());
+
+        let again = process_image(&source, "blog", "visit", "phone.jpg", None, &config, &AssetManifest::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(again.original_size, ImageSize::new(20, 40));
+        assert_eq!(exif_of(&again.original), Some(original));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_originals_keep_their_encoding() {
+        let dir = std::env::temp_dir().join(format!("site-builder-originals-{}", std::process::id()));
+        fs::create_dir_all(dir.join("content")).unwrap();
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(32, 24, |x, y| image::Rgb([(x * 8) as u8, (y * 10) as u8, 90])));
+        let exif = copyright_exif(&[b"II*\0".as_slice(), &[8, 0, 0, 0, 1, 0, 0x98, 0x82, 2, 0, 4, 0, 0, 0], b"Ada\0", &[0; 4]].concat()).unwrap();
+        let metadata = KeptMetadata {
+            icc_profile: Some(b"not really a profile".to_vec()),
+            exif: Some(exif),
+            orientation: None,
+        };
+
+        // A lossy WebP source with metadata, and a PNG one with a text chunk
+        let mut config = Config::default();
+        write_image(&config, &img, &dir.join("content/hype.webp"), &metadata).unwrap();
+        let png = fs::read({
+            img.save(dir.join("content/plain.png")).unwrap();
+            dir.join("content/plain.png")
+        })
+        .unwrap();
+        let text = png_chunk(b"tEXt", b"Comment\0taken at 59.91N 10.75E");
+        fs::write(dir.join("content/plain.png"), [&png[..33], &text, &png[33..]].concat()).unwrap();
+
+        config.public_dir = dir.join("public");
+        config.cache.directory = dir.join("cache");
+        config.images.formats = Vec::new();
+        config.images.placeholders.enabled = false;
+        for name in ["hype.webp", "plain.png"] {
+            process_image(&dir.join("content").join(name), "blog", "post", name, None, &config, &AssetManifest::default()).unwrap();
+        }
+
+        let source = fs::read(dir.join("content/hype.webp")).unwrap();
+        let original = fs::read(dir.join("public/images/blog/post-hype.webp")).unwrap();
+        let chunks = |data| webp_chunks(data).unwrap().into_iter().map(|(fourcc, data)| (fourcc, data.to_vec())).collect::<Vec<_>>();
+        let source = chunks(&source);
+        assert_eq!(source.iter().map(|(fourcc, _)| fourcc).collect::<Vec<_>>(), [b"VP8X", b"ICCP", b"VP8 ", b"EXIF"]);
+        // The same image data, with the metadata flags of the header cleared
+        let mut header = source[0].clone();
+        header.1[0] = 0;
+        assert_eq!(chunks(&original), [header, source[2].clone()]);
+
+        let original = fs::read(dir.join("public/images/blog/post-plain.png")).unwrap();
+        assert_eq!(original, png);
+        let mut decoder = ImageReader::new(Cursor::new(&original)).with_guessed_format().unwrap().into_decoder().unwrap();
+        assert_eq!(decoder.dimensions(), (32, 24));
+        assert_eq!(decoder.exif_metadata().unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_webp_is_lossy_at_the_configured_quality() {
+        let dir = std::env::temp_dir().join(format!("site-builder-webp-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| {
+            image::Rgb([((x * 37 + y * 91) % 251) as u8, (x * 4) as u8, (y * 5) as u8])
+        }));
+        let mut config = Config::default();
+        let mut write = |quality: u8, metadata: &KeptMetadata| {
+            config.images.quality.insert("webp".to_string(), quality);
+            let path = dir.join(format!("q{quality}.webp"));
+            write_image(&config, &img, &path, metadata).unwrap();
+            fs::read(path).unwrap()
+        };
+
+        let low = write(20, &KeptMetadata::default());
+        let high = write(95, &KeptMetadata::default());
+        assert!(low.len() < high.len());
+        let chunks = webp_chunks(&low).unwrap();
+        assert_eq!(chunks.iter().map(|(fourcc, _)| fourcc).collect::<Vec<_>>(), [b"VP8 "]);
+
+        // A TIFF header and one IFD with the copyright `Ada`
+        let exif = copyright_exif(&[b"II*\0".as_slice(), &[8, 0, 0, 0, 1, 0, 0x98, 0x82, 2, 0, 4, 0, 0, 0], b"Ada\0", &[0; 4]].concat()).unwrap();
+        let metadata = KeptMetadata {
+            icc_profile: Some(b"not really a profile".to_vec()),
+            exif: Some(exif.clone()),
+            orientation: None,
+        };
+        let kept = write(50, &metadata);
+        let fourccs: Vec<[u8; 4]> = webp_chunks(&kept).unwrap().into_iter().map(|(fourcc, _)| fourcc).collect();
+        assert_eq!(fourccs, [*b"VP8X", *b"ICCP", *b"VP8 ", *b"EXIF"]);
+        let mut decoder = ImageReader::new(Cursor::new(&kept)).with_guessed_format().unwrap().into_decoder().unwrap();
+        assert_eq!(decoder.dimensions(), (64, 48));
+        assert_eq!(decoder.icc_profile().unwrap(), metadata.icc_profile);
+        assert_eq!(decoder.exif_metadata().unwrap(), Some(exif));
+        assert!(webp_with_metadata(&low, 0, 48, &metadata).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cover_crops_follow_the_focus() {
+        // Flat grey on the left, a busy pattern on the right
+        let img = DynamicImage::ImageLuma8(GrayImage::from_fn(200, 100, |x, y| {
+            image::Luma([if x < 100 { 128 } else { ((x * 37 + y * 91) % 251) as u8 }])
+        }));
+
+        assert_eq!(crop_window(&img, 100, 100, Focus::Center), (50, 0, 100, 100));
+        assert_eq!(crop_window(&img, 100, 100, Focus::Point(0.0, 0.5)), (0, 0, 100, 100));
+        assert_eq!(crop_window(&img, 100, 100, Focus::Point(0.7, 0.5)), (90, 0, 100, 100));
+        assert_eq!(crop_window(&img, 100, 100, Focus::Entropy), (100, 0, 100, 100));
+        // A wider target crops the height instead
+        assert_eq!(crop_window(&img, 400, 100, Focus::Point(0.5, 0.0)), (0, 0, 200, 50));
+
+        let preset = ImagePreset {
+            name: "cover".to_string(),
+            width: 30,
+            height: Some(20),
+            fit: FitMode::Cover,
+        };
+        let resized = resize(&img, &preset, Focus::Entropy, FilterType::Triangle);
+        assert_eq!(resized.dimensions(), (30, 20));
+
+        assert_eq!(parse_focus("0.3, 0.6").unwrap(), (0.3, 0.6));
+        assert!(parse_focus("0.3").is_err());
+        assert!(parse_focus("1.5,0.5").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::{self, File};
+
+use crate::config::Config;
+use crate::output;
+use crate::feeds::{absolutize_urls, feed_entries, FeedEntry};
+use crate::CollectionBuild;
+
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// Top-level JSON Feed 1.1 object
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    language: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    content_html: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    date_published: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_modified: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+}
+
+/// Write `feed.json` and, for large archives, `feed-2.json`, `feed-3.json`, ...
+/// linked through `next_url`. Returns the number of pages written.
+pub fn write_json_feed(config: &Config, builds: &[CollectionBuild]) -> Result<usize> {
+    if !config.feeds.json_feed {
+        return Ok(0);
+    }
+    if config.site.url.is_none() {
+        eprintln!("⚠️  Skipping JSON Feed: `site.url` is not set");
+        return Ok(0);
+    }
+
+    let entries = feed_entries(config, builds);
+    let site_url = config.absolute_url("/").unwrap_or_default();
+    let page_size = config.feeds.json_feed_page_size.max(1);
+    let pages: Vec<&[FeedEntry]> = if entries.is_empty() {
+        vec![&[]]
+    } else {
+        entries.chunks(page_size).collect()
+    };
+
+    for (i, page) in pages.iter().enumerate() {
+        let feed = JsonFeed {
+            version: JSON_FEED_VERSION,
+            title: &config.site.title,
+            home_page_url: site_url.clone(),
+            feed_url: config.absolute_url(&page_file_name(i)).unwrap_or_default(),
+            description: config.site.description.as_deref(),
+            language: &config.site.language,
+            authors: config
+                .site
+                .author
+                .as_deref()
+                .map(|name| vec![JsonFeedAuthor { name }])
+                .unwrap_or_default(),
+            next_url: (i + 1 < pages.len())
+                .then(|| config.absolute_url(&page_file_name(i + 1)).unwrap_or_default()),
+            items: page
+                .iter()
+                .map(|entry| {
+                    let post = entry.post;
+                    JsonFeedItem {
+                        id: &entry.url,
+                        url: &entry.url,
+                        title: &post.title,
+                        content_html: absolutize_urls(&post.content, &site_url),
+                        summary: post.description.as_deref(),
+                        image: post.cover.as_ref().and_then(|c| config.absolute_url(&c.cover)),
+                        date_published: post.date,
+                        date_modified: post.updated,
+                        tags: &post.tags,
+                        language: post.language.as_deref(),
+                    }
+                })
+                .collect(),
+        };
+
+        let path = config.public_dir.join(page_file_name(i));
+        fs::create_dir_all(&config.public_dir)
+            .with_context(|| format!("Failed to create public directory: {}", config.public_dir.display()))?;
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create JSON Feed: {}", path.display()))?;
+        output::to_writer(config, file, &feed)
+            .with_context(|| format!("Failed to write JSON Feed to: {}", path.display()))?;
+    }
+
+    Ok(pages.len())
+}
+
+fn page_file_name(page: usize) -> String {
+    if page == 0 {
+        "feed.json".to_string()
+    } else {
+        format!("feed-{}.json", page + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Post;
+
+    #[test]
+    fn test_large_archives_are_paginated() {
+        let dir = std::env::temp_dir().join(format!("site-builder-json-feed-{}", std::process::id()));
+        let mut config = Config {
+            public_dir: dir.clone(),
+            ..Default::default()
+        };
+        config.site.url = Some("https://example.com".to_string());
+        config.feeds.json_feed_page_size = 2;
+        let post = |slug: &str, day: u32| Post {
+            title: slug.to_string(),
+            slug: slug.to_string(),
+            date: format!("2024-03-{day:02}T12:00:00Z").parse().unwrap(),
+            content: r#"<img src="/images/a.png">"#.to_string(),
+            tags: vec!["rust".to_string()],
+            ..Default::default()
+        };
+        let builds = [CollectionBuild {
+            collection: config.collections().remove(0),
+            posts: vec![post("first", 1), post("third", 3), post("second", 2)],
+            processed: 3,
+            skipped: 0,
+            errors: Vec::new(),
+        }];
+
+        assert_eq!(write_json_feed(&config, &builds).unwrap(), 2);
+        let read = |name: &str| -> serde_json::Value {
+            serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap()
+        };
+        let first = read("feed.json");
+        assert_eq!(first["version"], JSON_FEED_VERSION);
+        assert_eq!(first["feed_url"], "https://example.com/feed.json");
+        assert_eq!(first["next_url"], "https://example.com/feed-2.json");
+        let items: Vec<&str> = first["items"].as_array().unwrap().iter().map(|i| i["url"].as_str().unwrap()).collect();
+        assert_eq!(items, ["https://example.com/blog/third", "https://example.com/blog/second"]);
+        assert_eq!(first["items"][0]["content_html"], r#"<img src="https://example.com/images/a.png">"#);
+        assert_eq!(first["items"][0]["tags"][0], "rust");
+
+        let last = read("feed-2.json");
+        assert!(last.get("next_url").is_none());
+        assert_eq!(last["items"][0]["id"], "https://example.com/blog/first");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
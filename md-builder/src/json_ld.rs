@@ -0,0 +1,135 @@
+use hayagriva::types::EntryType;
+use hayagriva::Entry;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::seo::{SeoInput, SeoMetadata};
+
+/// Schema.org data for a post: a `BlogPosting`, or a `ScholarlyArticle` when it cites
+/// bibliography entries, which are listed under `citation`
+pub fn build(config: &Config, input: &SeoInput, seo: &SeoMetadata, entries: &[Entry]) -> Value {
+    let url = config.absolute_url(input.path);
+    let mut data = json!({
+        "@context": "https://schema.org",
+        "@type": if entries.is_empty() { "BlogPosting" } else { "ScholarlyArticle" },
+        "headline": input.title,
+        "description": input.description,
+        "url": url,
+        "mainEntityOfPage": seo.canonical_url.as_ref().or(url.as_ref()),
+        "datePublished": input.date,
+        "dateModified": input.updated.unwrap_or(input.date),
+        "inLanguage": input.language.unwrap_or(&config.site.language),
+        "keywords": input.tags,
+        "image": seo.open_graph.image,
+        "author": config.site.author.as_ref().map(|name| json!({ "@type": "Person", "name": name })),
+        "publisher": {
+            "@type": "Organization",
+            "name": config.site.title,
+            "url": config.site.url,
+        },
+        "citation": entries.iter().map(citation).collect::<Vec<_>>(),
+    });
+    prune(&mut data);
+    data
+}
+
+/// Schema.org representation of a bibliography entry
+fn citation(entry: &Entry) -> Value {
+    let schema_type = match entry.entry_type() {
+        EntryType::Article => "ScholarlyArticle",
+        EntryType::Book | EntryType::Anthology => "Book",
+        EntryType::Chapter => "Chapter",
+        EntryType::Thesis => "Thesis",
+        EntryType::Report => "Report",
+        EntryType::Web | EntryType::Blog => "WebPage",
+        EntryType::Repository => "SoftwareSourceCode",
+        EntryType::Video => "VideoObject",
+        _ => "CreativeWork",
+    };
+    let authors: Vec<Value> = entry
+        .authors()
+        .unwrap_or_default()
+        .iter()
+        .map(|person| json!({ "@type": "Person", "name": person.given_first(false) }))
+        .collect();
+    let part_of = entry.parents().first().and_then(|parent| {
+        parent
+            .title()
+            .map(|title| json!({ "@type": "CreativeWork", "name": title.to_string() }))
+    });
+
+    json!({
+        "@type": schema_type,
+        "name": entry.title().map(|title| title.to_string()),
+        "author": authors,
+        "datePublished": entry.date_any().map(|date| date.to_string().trim_start_matches('~').to_string()),
+        "url": entry.url_any().map(|url| url.value.to_string()),
+        "sameAs": entry.doi().map(|doi| format!("https://doi.org/{doi}")),
+        "isPartOf": part_of,
+        "publisher": entry
+            .publisher()
+            .and_then(|publisher| publisher.name())
+            .map(|name| json!({ "@type": "Organization", "name": name.to_string() })),
+    })
+}
+
+/// Drop null values and empty arrays so the output only carries what is known
+pub fn prune(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|_, v| !v.is_null() && v.as_array().is_none_or(|a| !a.is_empty()));
+            fields.values_mut().for_each(prune);
+        }
+        Value::Array(items) => items.iter_mut().for_each(prune),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seo::{build_seo, SeoOverrides};
+
+    #[test]
+    fn test_posts_with_citations_are_scholarly_articles() {
+        let mut config = Config::default();
+        config.site.url = Some("https://example.com".to_string());
+        config.site.author = Some("Ada Lovelace".to_string());
+        let tags = vec!["rust".to_string()];
+        let input = SeoInput {
+            path: "/blog/hello",
+            title: "Hello",
+            description: None,
+            image: None,
+            date: "2024-03-20T12:00:00Z".parse().unwrap(),
+            updated: None,
+            language: None,
+            tags: &tags,
+        };
+        let seo = build_seo(&config, &input, SeoOverrides::default());
+
+        let plain = build(&config, &input, &seo, &[]);
+        assert_eq!(plain["@type"], "BlogPosting");
+        assert!(plain.get("citation").is_none());
+        assert!(plain.get("description").is_none());
+
+        let library = hayagriva::io::from_yaml_str(
+            "knuth:\n  type: article\n  title: Literate Programming\n  author: Knuth, Donald E.\n  date: 1984\n  serial-number:\n    doi: 10.1093/comjnl/27.2.97\n  parent:\n    type: periodical\n    title: The Computer Journal\n",
+        )
+        .unwrap();
+        let entries: Vec<Entry> = library.iter().cloned().collect();
+        let scholarly = build(&config, &input, &seo, &entries);
+        assert_eq!(scholarly["@type"], "ScholarlyArticle");
+        assert_eq!(scholarly["url"], "https://example.com/blog/hello");
+        assert_eq!(scholarly["author"]["name"], "Ada Lovelace");
+
+        let cited = &scholarly["citation"][0];
+        assert_eq!(cited["@type"], "ScholarlyArticle");
+        assert_eq!(cited["name"], "Literate Programming");
+        assert_eq!(cited["author"][0]["name"], "Donald E. Knuth");
+        assert_eq!(cited["datePublished"], "1984");
+        assert_eq!(cited["sameAs"], "https://doi.org/10.1093/comjnl/27.2.97");
+        assert_eq!(cited["isPartOf"]["name"], "The Computer Journal");
+        assert!(cited.get("url").is_none());
+    }
+}
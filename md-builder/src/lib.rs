@@ -1,17 +1,25 @@
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use syntect::parsing::SyntaxSet;
+#[cfg(target_arch = "wasm32")]
 use std::sync::OnceLock;
+#[cfg(target_arch = "wasm32")]
 use pulldown_cmark::{Event, Options, Parser as MarkdownParser, Tag, TagEnd, CodeBlockKind};
+#[cfg(target_arch = "wasm32")]
 use syntect::html::{ClassedHTMLGenerator, ClassStyle};
+#[cfg(target_arch = "wasm32")]
 use syntect::util::LinesWithEndings;
 
+#[cfg(target_arch = "wasm32")]
 static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
 
+#[cfg(target_arch = "wasm32")]
 fn get_syntax_set() -> &'static SyntaxSet {
-    SYNTAX_SET.get_or_init(|| SyntaxSet::load_defaults_newlines())
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
 }
 
+#[cfg(target_arch = "wasm32")]
 fn render_code_block_wasm(syntax_set: &SyntaxSet, language: &str, code: &str) -> String {
     if language.is_empty() {
         return format!("<pre><code>{}</code></pre>", html_escape::encode_text(code));
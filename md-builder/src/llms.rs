@@ -0,0 +1,247 @@
+use anyhow::{Context, Result};
+use pulldown_cmark::{Event, LinkType, Options, Parser as MarkdownParser, Tag};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::citations::CitationResult;
+use crate::config::Config;
+use crate::{is_indexed, post_path, CollectionBuild};
+
+/// What goes into the markdown copy of a post
+pub struct TextInput<'a> {
+    pub collection: &'a str,
+    pub slug: &'a str,
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    /// Markdown source with includes expanded, frontmatter still in place
+    pub source: &'a str,
+    pub citations: Option<&'a CitationResult>,
+    /// Left out of `llms.txt`, so no copy is published
+    pub noindex: bool,
+}
+
+/// Where the markdown copy of a post is written, `None` when its collection is not exported
+pub fn text_path(config: &Config, collection: &str, slug: &str) -> Option<PathBuf> {
+    let settings = &config.llms;
+    (settings.enabled && settings.collections.iter().any(|c| c == collection))
+        .then(|| config.public_dir.join(collection).join(format!("{slug}.md")))
+}
+
+/// Site path of the markdown copy, e.g. `/blog/intro.md`
+fn text_url(collection: &str, slug: &str) -> String {
+    format!("{}.md", post_path(collection, slug))
+}
+
+/// Write `<public_dir>/<collection>/<slug>.md`: the post's markdown without frontmatter,
+/// with citations replaced by their formatted text, a plain reference list and absolute URLs.
+/// Code blocks are left fenced as written.
+pub fn write_post_text(
+    config: &Config,
+    input: &TextInput,
+    resolve_url: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let Some(path) = text_path(config, input.collection, input.slug) else {
+        return Ok(());
+    };
+    if input.noindex {
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove post text: {}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    let mut text = format!("# {}\n\n", input.title);
+    if let Some(description) = input.description {
+        text.push_str(&format!("> {description}\n\n"));
+    }
+    let url = post_path(input.collection, input.slug);
+    text.push_str(&format!("Source: {}\n\n", config.absolute_url(&url).unwrap_or(url)));
+    text.push_str(clean_markdown(config, input.source, input.citations, resolve_url).trim());
+    text.push('\n');
+
+    if let Some(citations) = input.citations.filter(|c| !c.references.is_empty()) {
+        text.push_str("\n## References\n\n");
+        for reference in &citations.references {
+            text.push_str(&format!("- {reference}\n"));
+        }
+    }
+
+    write_file(&path, &text)
+}
+
+fn clean_markdown(
+    config: &Config,
+    source: &str,
+    citations: Option<&CitationResult>,
+    resolve_url: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    // Replacements of source ranges; code ranges are never touched
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut protected: Vec<Range<usize>> = Vec::new();
+
+    // The absolute URL for a link destination, `None` when it stays as written
+    let rewrite = |dest: &str| {
+        let url = resolve_url(dest).unwrap_or_else(|| dest.to_string());
+        let url = if url.starts_with('/') {
+            config.absolute_url(&url).unwrap_or(url)
+        } else {
+            url
+        };
+        (url != dest).then_some(url)
+    };
+    // Where `dest` is first written in `source[range]` after the last `marker`
+    let find_dest = |range: Range<usize>, marker: &str, dest: &str| {
+        let text = &source[range.clone()];
+        let after = text.rfind(marker)? + marker.len();
+        let start = range.start + after + text[after..].find(dest)?;
+        Some(start..start + dest.len())
+    };
+
+    let mut events = MarkdownParser::new_ext(source, options).into_offset_iter();
+    for (event, range) in events.by_ref() {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => {
+                protected.push(range.clone());
+                edits.push((range, String::new()));
+            }
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => protected.push(range),
+            // Reference links are rewritten at their definition below. Autolinks need no
+            // rewriting, since only absolute URLs can be autolinks.
+            Event::Start(Tag::Link { link_type: LinkType::Inline, dest_url, .. })
+            | Event::Start(Tag::Image { link_type: LinkType::Inline, dest_url, .. }) => {
+                if let (Some(url), Some(dest)) = (rewrite(&dest_url), find_dest(range, "](", &dest_url)) {
+                    edits.push((dest, url));
+                }
+            }
+            _ => {}
+        }
+    }
+    for (_, definition) in events.reference_definitions().iter() {
+        if let (Some(url), Some(dest)) = (rewrite(&definition.dest), find_dest(definition.span.clone(), "]:", &definition.dest)) {
+            edits.push((dest, url));
+        }
+    }
+
+    if let Some(citations) = citations {
+        let citation_regex = regex_lite::Regex::new(r"@\[([^\]]+)\]").unwrap();
+        for caps in citation_regex.captures_iter(source) {
+            let found = caps.get(0).unwrap();
+            if protected.iter().any(|r| r.contains(&found.start())) {
+                continue;
+            }
+            if let Some(citation) = citations.citations.get(&caps[1]) {
+                edits.push((found.range(), citation.clone()));
+            }
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut cleaned = String::with_capacity(source.len());
+    let mut last_end = 0;
+    for (range, replacement) in edits {
+        if range.start < last_end {
+            continue;
+        }
+        cleaned.push_str(&source[last_end..range.start]);
+        cleaned.push_str(&replacement);
+        last_end = range.end;
+    }
+    cleaned.push_str(&source[last_end..]);
+    cleaned
+}
+
+/// Write `llms.txt`, listing every exported post with its description and the URL of its
+/// markdown copy, and `llms-full.txt` with the full text of all of them. Posts are listed in
+/// index order. Returns the number of posts listed.
+pub fn write_llms_txt(config: &Config, builds: &[CollectionBuild]) -> Result<usize> {
+    let settings = &config.llms;
+    if !settings.enabled {
+        return Ok(0);
+    }
+
+    let mut header = format!("# {}\n", config.site.title);
+    if let Some(description) = &config.site.description {
+        header.push_str(&format!("\n> {description}\n"));
+    }
+    let mut index = header.clone();
+    let mut full = header;
+    let mut count = 0;
+
+    for build in builds
+        .iter()
+        .filter(|b| settings.collections.contains(&b.collection.name))
+    {
+        let collection = &build.collection.name;
+        index.push_str(&format!("\n## {collection}\n\n"));
+        for post in build.posts.iter().filter(|post| is_indexed(post)) {
+            let Some(path) = text_path(config, collection, &post.slug) else {
+                continue;
+            };
+            let url = text_url(collection, &post.slug);
+            let url = config.absolute_url(&url).unwrap_or(url);
+            index.push_str(&format!("- [{}]({url})", post.title.replace(']', "\\]")));
+            if let Some(description) = &post.description {
+                index.push_str(&format!(": {description}"));
+            }
+            index.push('\n');
+
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read post text: {}", path.display()))?;
+            full.push_str(&format!("\n{text}"));
+            count += 1;
+        }
+    }
+
+    write_file(&config.public_dir.join("llms.txt"), &index)?;
+    write_file(&config.public_dir.join("llms-full.txt"), &full)?;
+    Ok(count)
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_markdown_resolves_citations_outside_code() {
+        let mut config = Config::default();
+        config.site.url = Some("https://example.com".to_string());
+        let citations = CitationResult {
+            citations: [("knuth".to_string(), "(Knuth, 1984)".to_string())].into(),
+            ..Default::default()
+        };
+        let source = "---\ntitle: Hello\n---\n\nAs shown @[knuth], see [chart](chart.png) and [home](/about).\n\nAlso [the map][map], [About] and <https://example.org/x>.\n\n```rust\n// @[knuth]\n```\n\n[map]: ./map.png \"Map\"\n[about]: </about>\n";
+        let cleaned = clean_markdown(&config, source, Some(&citations), |url| {
+            match url {
+                "chart.png" => Some("/blog/hello/chart.png".to_string()),
+                "./map.png" => Some("/blog/hello/map.png".to_string()),
+                _ => None,
+            }
+        });
+
+        assert!(!cleaned.contains("title: Hello"));
+        assert!(cleaned.contains("As shown (Knuth, 1984), see"));
+        assert!(cleaned.contains("[chart](https://example.com/blog/hello/chart.png)"));
+        assert!(cleaned.contains("[home](https://example.com/about)"));
+        assert!(cleaned.contains("[map]: https://example.com/blog/hello/map.png \"Map\""));
+        assert!(cleaned.contains("[about]: <https://example.com/about>"));
+        assert!(cleaned.contains("<https://example.org/x>"));
+        assert!(cleaned.contains("```rust\n// @[knuth]\n```"));
+    }
+}
@@ -1,25 +1,50 @@
 use anyhow::{Context, Result};
+use assets::AssetManifest;
+use bundles::{PageBundle, BUNDLE_INDEX_FILE};
 use chrono::{DateTime, Utc};
 use citations::{parse_markdown_with_citations, CitationConfig};
 #[cfg(feature = "cli")]
-use clap::Parser;
-use config::Config;
-use image::ImageFormat;
+use clap::{Parser, Subcommand};
+use config::{CollectionSettings, Config};
 use indicatif::{ProgressBar, ProgressStyle};
 use pulldown_cmark::{Event, MetadataBlockKind, Options, Parser as MarkdownParser, Tag, TagEnd, CodeBlockKind};
 use rayon::prelude::*;
+use schemars::JsonSchema;
+use sections::{SectionSummary, SECTION_INDEX_FILE};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use syntect::html::{ClassedHTMLGenerator, ClassStyle};
 use syntect::parsing::SyntaxSet;
 use syntect::util::LinesWithEndings;
+use ts_rs::TS;
 use walkdir::WalkDir;
+mod assets;
+mod blurhash;
+mod bundles;
+mod cite;
 mod citations;
+mod collections;
 mod config;
 mod csl_client;
+mod epub;
+mod feeds;
+mod images;
+mod json_feed;
+mod json_ld;
+mod llms;
+mod og_image;
+mod output;
+mod pagination;
+mod prerender;
+mod sitemap;
+mod schema;
+mod search;
+mod sections;
+mod seo;
+mod typescript;
 mod zotero;
 
 pub fn render_code_block(syntax_set: &SyntaxSet, language: &str, code: &str) -> String {
@@ -52,55 +77,137 @@ pub fn render_code_block(syntax_set: &SyntaxSet, language: &str, code: &str) ->
 }
 
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize, TS, JsonSchema)]
 struct Post {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    #[serde(default)]
+    schema_version: u32,
     title: String,
     date: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    updated: Option<DateTime<Utc>>,
     slug: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    section: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    language: Option<String>,
+    /// Shared by all language versions of the same post
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    translation_key: Option<String>,
     content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
     description: Option<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// Always present, empty for posts without tags
+    #[serde(default)]
     tags: Vec<String>,
+    /// Name of the series the post belongs to, e.g. for EPUB exports
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    series: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
     cover: Option<CoverImage>,
+    /// Canonical URL, Open Graph and Twitter card metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    seo: Option<seo::SeoMetadata>,
+    /// Schema.org `BlogPosting` or `ScholarlyArticle` data for a JSON-LD script
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    json_ld: Option<serde_json::Value>,
+    /// BibTeX, RIS and CSL-JSON records for citing the post
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    cite: Option<cite::PostCitation>,
+    /// Frontmatter fields declared in the collection schema
+    #[serde(flatten)]
+    fields: BTreeMap<String, serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, TS, JsonSchema)]
 struct CoverImage {
     original: String,
+    original_size: images::ImageSize,
     cover: String,
     thumbnail: String,
+    /// Every preset in the source format, narrowest first, for the `<img>` `srcset`
+    srcset: String,
+    /// Every preset in the source format with its pixel size, narrowest first
+    variants: Vec<images::ImageVariant>,
+    /// Every preset in the configured extra formats, for `<picture>` sources
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sources: Vec<images::ImageSource>,
+    /// BlurHash, LQIP and dominant color to show until the cover has loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    placeholder: Option<images::ImagePlaceholder>,
 }
 
 #[derive(Default, Deserialize)]
 struct PostMetadata {
     title: Option<String>,
     date: Option<DateTime<Utc>>,
+    updated: Option<DateTime<Utc>>,
+    language: Option<String>,
+    translation_key: Option<String>,
     description: Option<String>,
     tags: Option<String>,
+    series: Option<String>,
     cover: Option<String>,
+    /// Point of the cover to keep when a preset crops it, as `x,y` fractions (`0.3,0.6`)
+    cover_focus: Option<String>,
+    seo: Option<seo::SeoOverrides>,
+    #[serde(flatten)]
+    extra: BTreeMap<String, serde_yaml::Value>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, TS, JsonSchema)]
 struct PostIndex {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sections: Vec<SectionSummary>,
     posts: Vec<PostSummary>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize, TS, JsonSchema)]
 struct PostSummary {
     title: String,
     date: DateTime<Utc>,
     slug: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    section: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    language: Option<String>,
     description: Option<String>,
     tags: Vec<String>,
     cover: Option<CoverImage>,
+    /// Schema fields listed in the collection's `index_fields`
+    #[serde(flatten)]
+    fields: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Debug)]
 struct ProcessingTask {
     source_path: std::path::PathBuf,
     output_path: std::path::PathBuf,
+    /// Path-based slug relative to the content directory, e.g. `blog/intro`
+    slug: String,
+    /// Section path of the containing directory, empty for the content root
+    section: String,
+    /// Directory of the page bundle when the post is a bundle's `index.md`
+    bundle_dir: Option<PathBuf>,
 }
 
 struct ProcessingStats {
@@ -108,6 +215,16 @@ struct ProcessingStats {
     skipped: usize,
 }
 
+/// Result of building one collection, kept around for site-wide outputs
+struct CollectionBuild {
+    collection: CollectionSettings,
+    /// Successfully built posts in the collection's sort order
+    posts: Vec<Post>,
+    processed: usize,
+    skipped: usize,
+    errors: Vec<anyhow::Error>,
+}
+
 #[cfg(feature = "cli")]
 #[derive(Parser)]
 #[command(name = "site-builder")]
@@ -129,25 +246,69 @@ pub struct Cli {
     /// Force rebuild all files (ignore incremental processing)
     #[arg(short, long)]
     pub force: bool,
+    
+    /// Optimize output for deployment: compact JSON, minified HTML and precompressed files.
+    /// Implies --force.
+    #[arg(long)]
+    pub production: bool,
+    
+    #[command(subcommand)]
+    pub command: Option<Command>,
 }
 
-fn sanitize_filename(filename: &str) -> String {
-    // Remove the file extension if present
-    if let Some(stem) = Path::new(filename).file_stem() {
-        stem.to_string_lossy().to_string()
-    } else {
-        filename.to_string()
-    }
+#[cfg(feature = "cli")]
+#[derive(Subcommand)]
+pub enum Command {
+    /// Generate TypeScript types for the JSON output from the Rust structs
+    Types {
+        /// TypeScript file to write
+        #[arg(short, long, default_value = "../src/types/blog.ts")]
+        output: PathBuf,
+        
+        /// Only check that the file is up to date, exit with an error if it is stale
+        #[arg(long)]
+        check: bool,
+    },
+    
+    /// Write prerendered HTML pages into the built app. Run after `vite build`
+    Prerender,
+    
+    /// Export posts to another format without touching the site output
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
 }
 
-fn get_filter_type(filter_type: &str) -> image::imageops::FilterType {
-    match filter_type {
-        "Nearest" => image::imageops::FilterType::Nearest,
-        "Triangle" => image::imageops::FilterType::Triangle,
-        "CatmullRom" => image::imageops::FilterType::CatmullRom,
-        "Gaussian" => image::imageops::FilterType::Gaussian,
-        "Lanczos3" | _ => image::imageops::FilterType::Lanczos3,
-    }
+#[cfg(feature = "cli")]
+#[derive(Subcommand)]
+pub enum ExportFormat {
+    /// Assemble posts into an EPUB 3 book. Without a selection the whole collection is exported.
+    Epub {
+        /// Collection to export from
+        #[arg(long, default_value = "blog")]
+        collection: String,
+        
+        /// Only posts whose `series` frontmatter matches
+        #[arg(long)]
+        series: Option<String>,
+        
+        /// Only posts with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        
+        /// Posts by slug in reading order, comma-separated or repeated
+        #[arg(long = "slug", value_delimiter = ',')]
+        slugs: Vec<String>,
+        
+        /// Book title, defaults to the series name or the site title
+        #[arg(long)]
+        title: Option<String>,
+        
+        /// EPUB file to write
+        #[arg(short, long, default_value = "export.epub")]
+        output: PathBuf,
+    },
 }
 
 fn should_process_file(source_path: &Path, output_path: &Path, force: bool) -> Result<bool> {
@@ -169,135 +330,40 @@ fn should_process_file(source_path: &Path, output_path: &Path, force: bool) -> R
     Ok(source_modified > output_modified)
 }
 
-fn check_image_needs_processing(
-    source_path: &Path,
-    output_dir: &Path,
-    slug: &str,
-    filename: &str,
-) -> Result<bool> {
-    let base_name = sanitize_filename(filename);
-    let final_name = format!("{slug}-{base_name}");
-    
-    // Get the extension from the source file, defaulting to jpg
-    let ext = source_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("jpg");
-    
-    // Check if any of the output images need updating
-    let original_path = output_dir.join(format!("{final_name}.{ext}"));
-    let cover_path = output_dir.join(format!("{final_name}-cover.{ext}"));
-    let thumb_path = output_dir.join(format!("{final_name}-thumb.{ext}"));
-    
-    // If any output file doesn't exist or is older than source, we need to process
-    for output_path in [&original_path, &cover_path, &thumb_path] {
-        if should_process_file(source_path, output_path, false)? {
-            return Ok(true);
-        }
-    }
-    
-    Ok(false)
+/// Site path a post is published at, e.g. `/blog/intro`
+fn post_path(collection: &str, slug: &str) -> String {
+    format!("/{collection}/{slug}")
 }
 
-fn create_cover_image_paths(slug: &str, filename: &str, source_path: &Path) -> CoverImage {
-    let base_name = sanitize_filename(filename);
-    let final_name = format!("{slug}-{base_name}");
-    let ext = source_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("jpg");
-    
-    CoverImage {
-        original: format!("/images/blog/{final_name}.{ext}"),
-        cover: format!("/images/blog/{final_name}-cover.{ext}"),
-        thumbnail: format!("/images/blog/{final_name}-thumb.{ext}"),
-    }
+/// Whether a post may be listed for crawlers and readers: in the sitemap, feeds, search and
+/// `llms.txt`. Posts with `seo.noindex` are only reachable through their own URL.
+fn is_indexed(post: &Post) -> bool {
+    !post.seo.as_ref().is_some_and(|seo| seo.noindex)
 }
 
-fn process_single_image(
-    source_path: &Path,
-    output_path: &Path,
-    target_width: u32,
-    target_height: u32,
-    filter_type: image::imageops::FilterType,
-) -> Result<()> {
-    let img = image::open(source_path)
-        .with_context(|| format!("Failed to open image for processing: {}", source_path.display()))?;
-    
-    let resized = img.resize(target_width, target_height, filter_type);
-    
-    // Ensure parent directory exists
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
-    }
-    
-    let output_file = File::create(output_path)
-        .with_context(|| format!("Failed to create image output file: {}", output_path.display()))?;
-    let mut writer = BufWriter::new(output_file);
-    
-    let format = output_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .and_then(|e| ImageFormat::from_extension(e))
-        .unwrap_or(ImageFormat::Jpeg);
-    
-    resized.write_to(&mut writer, format)
-        .with_context(|| format!("Failed to write processed image to: {}", output_path.display()))?;
-    
-    Ok(())
+/// Whether `tag` looks like a BCP 47 language tag such as `en`, `nb-NO` or `zh-Hant-TW`.
+/// Languages name output files, so anything else is rejected.
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric()))
 }
 
-fn process_image(source_path: &Path, slug: &str, filename: &str, config: &Config) -> Result<Option<CoverImage>> {
-    // Create image output directory in public folder
-    let image_dir = config.public_dir.join("images/blog");
-    fs::create_dir_all(&image_dir)?;
-
-    // Check if image processing is needed (incremental processing)
-    if !check_image_needs_processing(source_path, &image_dir, slug, filename)? {
-        // Return existing paths without processing
-        return Ok(Some(create_cover_image_paths(slug, filename, source_path)));
-    }
-
-    // Note: We can't access CLI here easily, so we'll always show this message for now
-    
-    let base_name = sanitize_filename(filename);
-    let final_name = format!("{slug}-{base_name}");
-    let ext = source_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("jpg");
-
-    // Define output paths
-    let original_path = image_dir.join(format!("{final_name}.{ext}"));
-    let cover_path = image_dir.join(format!("{final_name}-cover.{ext}"));
-    let thumb_path = image_dir.join(format!("{final_name}-thumb.{ext}"));
-
-    let filter_type = get_filter_type(&config.images.filter_type);
-
-    // Process images in parallel
-    let processing_tasks = vec![
-        (source_path, &original_path, None), // Original (no resize)
-        (source_path, &cover_path, Some((config.images.cover_width, config.images.cover_height))),
-        (source_path, &thumb_path, Some((config.images.thumbnail_width, config.images.thumbnail_height))),
-    ];
-
-    processing_tasks
-        .into_par_iter()
-        .try_for_each(|(src, dst, resize_params)| -> Result<()> {
-            if let Some((width, height)) = resize_params {
-                process_single_image(src, dst, width, height, filter_type)
-            } else {
-                // Copy original
-                fs::copy(src, dst).context("Failed to copy original image")?;
-                Ok(())
-            }
-        })?;
-
-    Ok(Some(create_cover_image_paths(slug, filename, source_path)))
+/// Language of a post, or the site language when it has none
+fn post_language<'a>(config: &'a Config, post: &'a Post) -> &'a str {
+    post.language
+        .as_deref()
+        .filter(|language| is_language_tag(language))
+        .unwrap_or(&config.site.language)
 }
 
-pub fn parse_metadata_and_content(content: &str, syntax_set: &SyntaxSet) -> Result<(PostMetadata, String)> {
+pub(crate) fn parse_metadata_and_content(
+    content: &str,
+    syntax_set: &SyntaxSet,
+    resolve_url: impl Fn(&str) -> Option<String>,
+) -> Result<(PostMetadata, String)> {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
     options.insert(Options::ENABLE_STRIKETHROUGH);
@@ -337,6 +403,8 @@ pub fn parse_metadata_and_content(content: &str, syntax_set: &SyntaxSet) -> Resu
     let mut in_code_block = false;
     let mut code_block_lang = String::new();
     let mut code_block_content = String::new();
+    // Source, title and collected alt text of the image being rendered
+    let mut image: Option<(String, String, String)> = None;
     
     for event in content_events {
         match event {
@@ -353,9 +421,41 @@ pub fn parse_metadata_and_content(content: &str, syntax_set: &SyntaxSet) -> Resu
             Event::Text(text) if in_code_block => {
                 code_block_content.push_str(&text);
             }
+            Event::Text(text) | Event::Code(text) if image.is_some() => {
+                if let Some((_, _, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                }
+            }
             Event::Code(code) => {
                 html_output.push_str(&format!("<code>{}</code>", html_escape::encode_text(&code)));
             }
+            // Point relative images and links at their published location. Images are
+            // rendered here because their alt text spans several events.
+            Event::Start(Tag::Image { dest_url, title, .. }) => {
+                let src = resolve_url(&dest_url).unwrap_or_else(|| dest_url.to_string());
+                image = Some((src, title.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((src, title, alt)) = image.take() {
+                    html_output.push_str(&format!(
+                        "<img src=\"{}\" alt=\"{}\"",
+                        html_escape::encode_double_quoted_attribute(&src),
+                        html_escape::encode_double_quoted_attribute(&alt)
+                    ));
+                    if !title.is_empty() {
+                        html_output.push_str(&format!(
+                            " title=\"{}\"",
+                            html_escape::encode_double_quoted_attribute(&title)
+                        ));
+                    }
+                    html_output.push_str(" />");
+                }
+            }
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) if !in_code_block => {
+                let dest_url = resolve_url(&dest_url).map(Into::into).unwrap_or(dest_url);
+                let event = Event::Start(Tag::Link { link_type, dest_url, title, id });
+                pulldown_cmark::html::push_html(&mut html_output, std::iter::once(event));
+            }
             _ => {
                 if !in_code_block {
                     pulldown_cmark::html::push_html(&mut html_output, std::iter::once(event));
@@ -368,79 +468,87 @@ pub fn parse_metadata_and_content(content: &str, syntax_set: &SyntaxSet) -> Resu
 }
 
 fn process_file(
-    path: &Path,
+    task: &ProcessingTask,
+    collection: &CollectionSettings,
     config: &Config,
     syntax_set: &SyntaxSet,
+    assets: &AssetManifest,
 ) -> Result<Post> {
+    let path = task.source_path.as_path();
     let content = fs::read_to_string(path)
         .with_context(|| format!("Failed to read file: {}", path.display()))?;
 
-    let (metadata, html_content) = parse_metadata_and_content(&content, syntax_set)?;
-
-    // Process the content with citations if configuration is available
-    let html_content = match (&config.citations.bibliography_path, config.get_zotero_config()) {
-        (Some(bib_path), None) => {
-            let cache_dir = if config.cache.enabled {
-                Some(config.cache.directory.clone())
-            } else {
-                None
-            };
-            let citation_config = CitationConfig::new(
-                &config.citations.style,
-                &config.citations.language_code,
-                bib_path,
-                cache_dir,
-            )?;
-            let citation_result = parse_markdown_with_citations(&html_content, &citation_config)?;
-            if citation_result.has_citations {
-                format!(
-                    "{}\n{}",
-                    citation_result.html_content, citation_result.references_html
-                )
+    let bundle = task
+        .bundle_dir
+        .as_deref()
+        .map(PageBundle::load)
+        .transpose()?
+        .map(|bundle| {
+            if config.assets.fingerprint {
+                bundle.with_fingerprints(config.assets.hash_length)
             } else {
-                citation_result.html_content
+                bundle
             }
+        });
+
+    // Only bundles include files, from their own directory
+    let content = match &task.bundle_dir {
+        Some(bundle_dir) => bundles::expand_includes(&content, bundle_dir)
+            .with_context(|| format!("Failed to expand includes in: {}", path.display()))?,
+        None => content,
+    };
+
+    let (metadata, html_content) = parse_metadata_and_content(&content, syntax_set, |url| {
+        bundle.as_ref().and_then(|b| b.resource_url(&collection.name, &task.slug, url))
+    })?;
+
+    // Load the citation configuration if one is available
+    let cache_dir = config.cache.enabled.then(|| config.cache.directory.clone());
+    let citation_config = match (&config.citations.bibliography_path, config.get_zotero_config()) {
+        (Some(bib_path), None) => Some(CitationConfig::new(
+            &config.citations.style,
+            &config.citations.language_code,
+            bib_path,
+            cache_dir,
+        )?),
+        (None, Some((api_key, user_id, collection_key))) => Some(CitationConfig::from_zotero(
+            &config.citations.style,
+            &config.citations.language_code,
+            &api_key,
+            &user_id,
+            collection_key.as_deref(),
+            cache_dir,
+        )?),
+        // No citation configuration provided, use content as-is
+        _ => None,
+    };
+    let citation_result = citation_config
+        .as_ref()
+        .map(|citation_config| parse_markdown_with_citations(&html_content, citation_config))
+        .transpose()?;
+    let html_content = match &citation_result {
+        Some(result) if result.has_citations => {
+            format!("{}\n{}", result.html_content, result.references_html)
         }
-        (None, Some((api_key, user_id, collection_key))) => {
-            let cache_dir = if config.cache.enabled {
-                Some(config.cache.directory.clone())
-            } else {
-                None
-            };
-            let citation_config = CitationConfig::from_zotero(
-                &config.citations.style,
-                &config.citations.language_code,
-                &api_key,
-                &user_id,
-                collection_key.as_deref(),
-                cache_dir,
-            )?;
-            let citation_result = parse_markdown_with_citations(&html_content, &citation_config)?;
-            if citation_result.has_citations {
-                format!(
-                    "{}\n{}",
-                    citation_result.html_content, citation_result.references_html
-                )
-            } else {
-                citation_result.html_content
-            }
-        }
-        _ => {
-            // No citation configuration provided, use content as-is
-            html_content
-        }
+        Some(result) => result.html_content.clone(),
+        None => html_content,
     };
 
-    let slug = path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("untitled")
-        .to_string();
+    let slug = task.slug.clone();
 
     let cover = if let Some(cover_filename) = metadata.cover {
-        let image_path = path.parent().unwrap_or(&config.content_dir).join(&cover_filename);
+        let image_path = bundle
+            .as_ref()
+            .and_then(|b| b.resolve(&cover_filename))
+            .unwrap_or_else(|| path.parent().unwrap_or(&collection.content_dir).join(&cover_filename));
+        let focus = metadata
+            .cover_focus
+            .as_deref()
+            .map(images::parse_focus)
+            .transpose()
+            .with_context(|| format!("Invalid cover_focus in: {}", path.display()))?;
         if image_path.exists() {
-            process_image(&image_path, &slug, &cover_filename, config)?
+            images::process_image(&image_path, &collection.name, &slug, &cover_filename, focus, config, assets)?
         } else {
             None
         }
@@ -448,86 +556,241 @@ fn process_file(
         None
     };
 
+    // A bundle-relative SEO image is published like any other referenced resource
+    let mut seo_overrides = metadata.seo.unwrap_or_default();
+    if let (Some(bundle), Some(image)) = (&bundle, &seo_overrides.image) {
+        if let Some(url) = bundle.resource_url(&collection.name, &slug, image) {
+            seo_overrides.image = Some(url);
+        }
+    }
+
+    // Only the bundle resources the post links to end up in the public directory
+    if let Some(bundle) = &bundle {
+        bundle.publish(&collection.name, &slug, config, assets)?;
+    }
+
+    if let Some(language) = metadata.language.as_deref().filter(|l| !is_language_tag(l)) {
+        anyhow::bail!("`language: {language}` is not a language tag like `en` or `nb-NO`");
+    }
+
+    let fields = collections::validate_fields(&collection.fields, &metadata.extra)
+        .with_context(|| format!("Frontmatter does not match the `{}` collection schema", collection.name))?;
+
     // Extract and process metadata with defaults
     let title = metadata.title.unwrap_or_else(|| "Untitled".to_string());
     let date = metadata.date.unwrap_or_else(Utc::now);
-    let tags = metadata
+    let tags: Vec<String> = metadata
         .tags
         .map(|t| t.split(',').map(|s| s.trim().to_string()).collect())
         .unwrap_or_default();
 
+    let share_image = if og_image::shares_card(config, cover.is_some()) {
+        let inputs = og_image::card_inputs(&og_image::style_hash(config)?, &title, &tags, &date)?;
+        Some(og_image::og_image_url(config, assets, &collection.name, &slug, &inputs)?)
+    } else {
+        cover.as_ref().map(|c| c.cover.clone())
+    };
+
+    let path_url = post_path(&collection.name, &slug);
+    let seo_input = seo::SeoInput {
+        path: &path_url,
+        title: &title,
+        description: metadata.description.as_deref(),
+        image: share_image.as_deref(),
+        date,
+        updated: metadata.updated,
+        language: metadata.language.as_deref(),
+        tags: &tags,
+    };
+    let seo = seo::build_seo(config, &seo_input, seo_overrides);
+    for warning in seo::validate(&seo) {
+        eprintln!("⚠️  SEO: {}: {warning}", path.display());
+    }
+    let cited_entries = citation_result.as_ref().map(|c| c.entries.as_slice()).unwrap_or_default();
+    let json_ld = json_ld::build(config, &seo_input, &seo, cited_entries);
+    let cite = cite::write_post_citation(
+        config,
+        &cite::CiteInput {
+            collection: &collection.name,
+            slug: &slug,
+            title: &title,
+            date,
+            language: metadata.language.as_deref(),
+        },
+        citation_config.as_ref(),
+        cited_entries,
+    )?;
+
+    llms::write_post_text(
+        config,
+        &llms::TextInput {
+            collection: &collection.name,
+            slug: &slug,
+            title: &title,
+            description: metadata.description.as_deref(),
+            source: &content,
+            citations: citation_result.as_ref(),
+            noindex: seo.noindex,
+        },
+        |url| bundle.as_ref().and_then(|b| b.resource_url(&collection.name, &slug, url)),
+    )?;
+
     Ok(Post {
+        schema_version: schema::SCHEMA_VERSION,
         title,
         date,
+        updated: metadata.updated,
         slug,
-        content: html_content,
+        section: task.section.clone(),
+        language: metadata.language,
+        translation_key: metadata.translation_key,
+        content: output::minify_html(config, html_content),
         description: metadata.description,
         tags,
+        series: metadata.series,
         cover,
+        seo: Some(seo),
+        json_ld: Some(json_ld),
+        cite,
+        fields,
     })
 }
 
-fn collect_processing_tasks(config: &Config) -> Result<Vec<ProcessingTask>> {
+fn collect_processing_tasks(collection: &CollectionSettings) -> Result<Vec<ProcessingTask>> {
     let mut tasks = Vec::new();
+
+    // Any directory below the content root with an `index.md` is a page bundle
+    let bundle_dirs = bundles::find_bundle_dirs(&collection.content_dir);
     
-    for entry in WalkDir::new(&config.content_dir)
+    for entry in WalkDir::new(&collection.content_dir)
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("md"))
+        .filter(|e| e.file_name() != SECTION_INDEX_FILE)
     {
         let source_path = entry.path().to_path_buf();
-        let slug = source_path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("untitled")
-            .to_string();
-        let output_path = config.output_dir.join(format!("{slug}.json"));
+        let bundle_dir = bundle_dirs
+            .iter()
+            .find(|dir| source_path.starts_with(dir))
+            .cloned();
+
+        // Other markdown files inside a bundle are resources, e.g. for includes
+        if bundle_dir.as_ref().is_some_and(|dir| source_path != dir.join(BUNDLE_INDEX_FILE)) {
+            continue;
+        }
+
+        // A bundle is addressed by its directory, a single file by its path without extension
+        let relative_path = bundle_dir
+            .as_deref()
+            .unwrap_or(&source_path)
+            .strip_prefix(&collection.content_dir)
+            .unwrap_or(&source_path);
+
+        // Nested directories map to sections, so `blog/intro.md` and `cases/intro.md`
+        // get distinct slugs and output files
+        let slug = sections::path_to_slug(&relative_path.with_extension(""));
+        let section = relative_path
+            .parent()
+            .map(sections::path_to_slug)
+            .unwrap_or_default();
+        let output_path = collection.output_dir.join(format!("{slug}.json"));
         
         tasks.push(ProcessingTask {
             source_path,
             output_path,
+            slug,
+            section,
+            bundle_dir,
         });
     }
     
     Ok(tasks)
 }
 
-fn process_task(task: &ProcessingTask, config: &Config, cli: &Cli, syntax_set: &SyntaxSet) -> Result<(Post, bool)> {
-    let needs_processing = should_process_file(&task.source_path, &task.output_path, cli.force)
-        .with_context(|| format!("Failed to check if file needs processing: {}", task.source_path.display()))?;
+fn task_needs_processing(task: &ProcessingTask, force: bool) -> Result<bool> {
+    let Some(bundle_dir) = &task.bundle_dir else {
+        return should_process_file(&task.source_path, &task.output_path, force);
+    };
+
+    // A bundle is stale when any of its files changed, not just the markdown
+    for source_path in PageBundle::load(bundle_dir)?.files() {
+        if should_process_file(&source_path, &task.output_path, force)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn write_post(config: &Config, output_path: &Path, post: &Post) -> Result<()> {
+    schema::validate(post, output_path)?;
+    // Posts in nested sections are written to matching subdirectories
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+    }
+    let post_file = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {}", output_path.display()))?;
+    output::to_writer(config, post_file, post)
+        .with_context(|| format!("Failed to write JSON to: {}", output_path.display()))?;
+    Ok(())
+}
+
+fn process_task(
+    task: &ProcessingTask,
+    collection: &CollectionSettings,
+    config: &Config,
+    cli: &Cli,
+    syntax_set: &SyntaxSet,
+    assets: &AssetManifest,
+) -> Result<(Post, bool)> {
+    let needs_processing = task_needs_processing(task, cli.force)
+        .with_context(|| format!("Failed to check if file needs processing: {}", task.source_path.display()))?
+        || cite::record_dir(config, &collection.name, &task.slug).is_some_and(|dir| !dir.join("cite.bib").exists());
     
     if needs_processing {
         if cli.verbose {
             println!("📝 Processing: {}", task.source_path.display());
         }
-        let post = process_file(&task.source_path, config, syntax_set)
+        let post = process_file(task, collection, config, syntax_set, assets)
             .with_context(|| format!("Failed to process markdown file: {}", task.source_path.display()))?;
         
         // Write individual post file
-        let post_file = File::create(&task.output_path)
-            .with_context(|| format!("Failed to create output file: {}", task.output_path.display()))?;
-        serde_json::to_writer_pretty(post_file, &post)
-            .with_context(|| format!("Failed to write JSON to: {}", task.output_path.display()))?;
+        write_post(config, &task.output_path, &post)?;
         
         Ok((post, true)) // true = processed
     } else {
         // File hasn't changed, load existing
         match fs::read_to_string(&task.output_path) {
             Ok(content) => {
-                match serde_json::from_str::<Post>(&content) {
-                    Ok(post) => Ok((post, false)), // false = skipped
-                    Err(json_err) => {
-                        // JSON is corrupted, reprocess
+                // Posts written with an older output format are rebuilt like corrupted ones
+                let cached = serde_json::from_str::<Post>(&content)
+                    .map_err(|e| e.to_string())
+                    .and_then(|post| match post.schema_version {
+                        schema::SCHEMA_VERSION => Ok(post),
+                        version => Err(format!("outdated schema version {version}")),
+                    })
+                    // Only listed posts get a markdown copy, so that is checked once the post is known
+                    .and_then(|post| match llms::text_path(config, &collection.name, &task.slug) {
+                        Some(path) if is_indexed(&post) && !path.exists() => Err("missing markdown copy".to_string()),
+                        _ => Ok(post),
+                    });
+                match cached {
+                    Ok(post) => {
+                        // Published copies may be gone even though the post is up to date
+                        if let Some(bundle_dir) = &task.bundle_dir {
+                            PageBundle::load(bundle_dir)?.republish(&collection.name, &task.slug, config, assets)?;
+                        }
+                        Ok((post, false)) // false = skipped
+                    }
+                    Err(reason) => {
+                        // JSON is corrupted or outdated, reprocess
                         if cli.verbose {
-                            eprintln!("⚠️  Warning: Corrupted JSON file ({}), reprocessing: {}", 
-                                    json_err, task.source_path.display());
+                            eprintln!("⚠️  Warning: Corrupted or outdated output ({}), reprocessing: {}", 
+                                    reason, task.source_path.display());
                         }
-                        let post = process_file(&task.source_path, config, syntax_set)
+                        let post = process_file(task, collection, config, syntax_set, assets)
                             .with_context(|| format!("Failed to reprocess markdown file: {}", task.source_path.display()))?;
-                        let post_file = File::create(&task.output_path)
-                            .with_context(|| format!("Failed to recreate output file: {}", task.output_path.display()))?;
-                        serde_json::to_writer_pretty(post_file, &post)
-                            .with_context(|| format!("Failed to write JSON to: {}", task.output_path.display()))?;
+                        write_post(config, &task.output_path, &post)?;
                         Ok((post, true))
                     }
                 }
@@ -537,50 +800,38 @@ fn process_task(task: &ProcessingTask, config: &Config, cli: &Cli, syntax_set: &
                 if cli.verbose {
                     println!("📝 Processing (missing output): {}", task.source_path.display());
                 }
-                let post = process_file(&task.source_path, config, syntax_set)
+                let post = process_file(task, collection, config, syntax_set, assets)
                     .with_context(|| format!("Failed to process markdown file: {}", task.source_path.display()))?;
-                let post_file = File::create(&task.output_path)
-                    .with_context(|| format!("Failed to create output file: {}", task.output_path.display()))?;
-                serde_json::to_writer_pretty(post_file, &post)
-                    .with_context(|| format!("Failed to write JSON to: {}", task.output_path.display()))?;
+                write_post(config, &task.output_path, &post)?;
                 Ok((post, true))
             }
         }
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn main() -> Result<()> {
-    dotenvy::dotenv().ok();
-    
-    // Parse CLI arguments
-    let cli = Cli::parse();
-    
-    if cli.verbose {
-        println!("🔧 Loading configuration from: {}", cli.config.display());
-    }
-    
-    // Load configuration from file or use defaults
-    let config = Arc::new(Config::load(&cli.config)
-        .context("Failed to load configuration. Check that config.yaml is valid or remove it to use defaults")?);
-    
-    // Initialize syntax set for code highlighting
-    let syntax_set = Arc::new(SyntaxSet::load_defaults_newlines());
-    
+/// Build one collection: process its markdown files in parallel and write the
+/// per-post JSON files, the section indexes and the collection index
+fn build_collection(
+    collection: CollectionSettings,
+    config: &Config,
+    cli: &Cli,
+    syntax_set: &SyntaxSet,
+    assets: &AssetManifest,
+) -> Result<CollectionBuild> {
     // Create output directory if it doesn't exist
-    fs::create_dir_all(&config.output_dir)
-        .with_context(|| format!("Failed to create output directory: {}", config.output_dir.display()))?;
+    fs::create_dir_all(&collection.output_dir)
+        .with_context(|| format!("Failed to create output directory: {}", collection.output_dir.display()))?;
 
     // Collect all processing tasks
-    let tasks = collect_processing_tasks(&config)?;
+    let tasks = collect_processing_tasks(&collection)?;
     
     if cli.verbose {
-        println!("📁 Content directory: {}", config.content_dir.display());
-        println!("📤 Output directory: {}", config.output_dir.display());
-        println!("🖼️  Image directory: {}", config.public_dir.join("images/blog").display());
+        println!("📁 Content directory: {}", collection.content_dir.display());
+        println!("📤 Output directory: {}", collection.output_dir.display());
+        println!("🖼️  Image directory: {}", config.public_dir.join("images").join(&collection.name).display());
     }
     
-    println!("Found {} markdown files", tasks.len());
+    println!("Found {} markdown files in {}", tasks.len(), collection.name);
     
     if cli.force {
         println!("🔄 Force rebuild enabled - processing all files");
@@ -607,7 +858,7 @@ fn main() -> Result<()> {
     let results: Vec<Result<Post>> = tasks
         .par_iter()
         .map(|task| {
-            let result = process_task(task, &config, &cli, &syntax_set);
+            let result = process_task(task, &collection, config, cli, syntax_set, assets);
             
             // Update progress bar
             if let Some(ref pb) = progress {
@@ -625,11 +876,7 @@ fn main() -> Result<()> {
                     Ok(post)
                 }
                 Err(e) => {
-                    if !cli.no_progress {
-                        eprintln!("Error processing {}: {}", task.source_path.display(), e);
-                    } else {
-                        eprintln!("Error processing {}: {}", task.source_path.display(), e);
-                    }
+                    eprintln!("Error processing {}: {}", task.source_path.display(), e);
                     // Continue processing other files instead of failing completely
                     Err(e)
                 }
@@ -661,47 +908,436 @@ fn main() -> Result<()> {
         }
         
         if posts.is_empty() {
-            return Err(anyhow::anyhow!("All files failed to process"));
+            return Err(anyhow::anyhow!("All files in {} failed to process", collection.name));
         } else {
             eprintln!("⚠️  Continuing with {} successfully processed posts", posts.len());
         }
     }
-    let stats = stats.lock().unwrap();
-    let (processed_count, skipped_count) = (stats.processed, stats.skipped);
+    let (processed_count, skipped_count) = {
+        let stats = stats.lock().unwrap();
+        (stats.processed, stats.skipped)
+    };
+
+    // Sort posts by the collection's sort key (newest first by default)
+    collections::sort_posts(&collection, &mut posts);
 
     // Convert to PostSummary for index
-    let mut post_summaries: Vec<PostSummary> = posts
-        .into_iter()
+    let post_summaries: Vec<PostSummary> = posts
+        .iter()
         .map(|post| PostSummary {
-            title: post.title,
+            title: post.title.clone(),
             date: post.date,
-            slug: post.slug,
-            description: post.description,
-            tags: post.tags,
-            cover: post.cover,
+            slug: post.slug.clone(),
+            section: post.section.clone(),
+            language: post.language.clone(),
+            description: post.description.clone(),
+            tags: post.tags.clone(),
+            cover: post.cover.clone(),
+            fields: collections::index_fields(&collection, &post.fields),
         })
         .collect();
 
-    // Sort posts by date (newest first)
-    post_summaries.sort_by(|a, b| b.date.cmp(&a.date));
-
     let len = post_summaries.len();
+    pagination::write_index_pages(config, &collection, &post_summaries)?;
+
+    // Build and write one index per section; the root section becomes the main index
+    let section_metadata = sections::load_section_metadata(&collection.content_dir, syntax_set)?;
+    let mut section_indexes = sections::build_section_indexes(&post_summaries, section_metadata);
+    sections::write_section_indexes(config, &collection.output_dir, &section_indexes)?;
+
+    let root = section_indexes
+        .iter()
+        .position(|s| s.path.is_empty())
+        .map(|i| section_indexes.swap_remove(i));
 
     // Write index file
-    let index = PostIndex { posts: post_summaries };
-    let index_path = config.output_dir.join("index.json");
+    let index = PostIndex {
+        schema_version: schema::SCHEMA_VERSION,
+        title: root.as_ref().map(|r| r.title.clone()).filter(|t| !t.is_empty()),
+        description: root.as_ref().and_then(|r| r.description.clone()),
+        sections: root.map(|r| r.subsections).unwrap_or_default(),
+        posts: post_summaries,
+    };
+    let index_path = collection.output_dir.join("index.json");
+    schema::validate(&index, &index_path)?;
     let index_file = File::create(&index_path)
         .with_context(|| format!("Failed to create index file: {}", index_path.display()))?;
-    serde_json::to_writer_pretty(index_file, &index)
+    output::to_writer(config, index_file, &index)
         .with_context(|| format!("Failed to write index JSON to: {}", index_path.display()))?;
 
-    println!("✨ Generated {len} posts ({processed_count} processed, {skipped_count} skipped)");
+    println!("✨ Generated {len} {} entries ({processed_count} processed, {skipped_count} skipped)", collection.name);
     
-    if !errors.is_empty() {
-        eprintln!("⚠️  Build completed with {} errors", errors.len());
+    Ok(CollectionBuild {
+        collection,
+        posts,
+        processed: processed_count,
+        skipped: skipped_count,
+        errors,
+    })
+}
+
+/// Process a collection's posts in memory for an export. Post JSON, indexes and the other site
+/// output are left alone; images are still published so the export can embed them.
+fn load_collection(
+    collection: CollectionSettings,
+    config: &Config,
+    syntax_set: &SyntaxSet,
+    assets: &AssetManifest,
+) -> Result<CollectionBuild> {
+    let tasks = collect_processing_tasks(&collection)?;
+    let results: Vec<Result<Post>> = tasks
+        .par_iter()
+        .map(|task| {
+            process_file(task, &collection, config, syntax_set, assets)
+                .with_context(|| format!("Failed to process markdown file: {}", task.source_path.display()))
+        })
+        .collect();
+
+    let (mut posts, mut errors) = (Vec::new(), Vec::new());
+    for result in results {
+        match result {
+            Ok(post) => posts.push(post),
+            Err(e) => {
+                eprintln!("Error: {e:#}");
+                errors.push(e);
+            }
+        }
+    }
+    if posts.is_empty() && !errors.is_empty() {
+        return Err(anyhow::anyhow!("All files in {} failed to process", collection.name));
+    }
+    collections::sort_posts(&collection, &mut posts);
+
+    Ok(CollectionBuild {
+        collection,
+        processed: posts.len(),
+        posts,
+        skipped: 0,
+        errors,
+    })
+}
+
+/// Read a collection's posts from the JSON the last build wrote, without processing anything
+fn read_collection(collection: CollectionSettings) -> Result<CollectionBuild> {
+    let mut posts = Vec::new();
+    for task in collect_processing_tasks(&collection)? {
+        let content = fs::read_to_string(&task.output_path)
+            .with_context(|| format!("Failed to read post (run the build first): {}", task.output_path.display()))?;
+        let post: Post = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse post: {}", task.output_path.display()))?;
+        if post.schema_version != schema::SCHEMA_VERSION {
+            anyhow::bail!(
+                "Post has schema version {}, expected {} (run the build first): {}",
+                post.schema_version,
+                schema::SCHEMA_VERSION,
+                task.output_path.display()
+            );
+        }
+        posts.push(post);
+    }
+    collections::sort_posts(&collection, &mut posts);
+
+    Ok(CollectionBuild {
+        collection,
+        posts,
+        processed: 0,
+        skipped: 0,
+        errors: Vec::new(),
+    })
+}
+
+/// Adjust the configuration for `--production` and the export commands
+#[cfg(not(target_arch = "wasm32"))]
+fn apply_cli_overrides(cli: &mut Cli, config: &mut Config) {
+    if cli.production {
+        // Cached posts were written with the development settings, so rebuild everything
+        cli.force = true;
+        config.output.compact_json = true;
+        config.output.minify_html = true;
+        config.output.precompress = true;
+    }
+    if matches!(cli.command, Some(Command::Export { .. })) {
+        // Exports only read posts, so the per-post Markdown and citation files are not rewritten.
+        // EPUB chapters must be well-formed XHTML, which minified HTML with unquoted attributes is not.
+        config.output.minify_html = false;
+        config.llms.enabled = false;
+        config.cite.enabled = false;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    
+    // Parse CLI arguments
+    let mut cli = Cli::parse();
+    
+    if let Some(Command::Types { output, check }) = &cli.command {
+        let up_to_date = typescript::write_types(output, *check)?;
+        match (up_to_date, check) {
+            (true, _) => println!("✅ TypeScript types are up to date: {}", output.display()),
+            (false, false) => println!("📝 Wrote TypeScript types to {}", output.display()),
+            (false, true) => {
+                eprintln!("❌ TypeScript types are stale: {}. Run `site-builder types` to update them", output.display());
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    
+    if cli.verbose {
+        println!("🔧 Loading configuration from: {}", cli.config.display());
+    }
+    
+    // Load configuration from file or use defaults
+    let mut config = Config::load(&cli.config)
+        .context("Failed to load configuration. Check that config.yaml is valid or remove it to use defaults")?;
+    apply_cli_overrides(&mut cli, &mut config);
+    let config = Arc::new(config);
+    
+    if let Some(Command::Prerender) = &cli.command {
+        let builds = config
+            .collections()
+            .into_iter()
+            .filter(|c| config.prerender.collections.contains(&c.name))
+            .map(read_collection)
+            .collect::<Result<Vec<_>>>()?;
+        let pages = prerender::write_pages(&config, &builds).context("Failed to prerender HTML pages")?;
+        println!("📄 Prerendered {pages} HTML page(s) into {}", config.prerender.output_dir.display());
+        if let Some(summary) = output::MINIFIED_HTML.summary() {
+            println!("🧹 Minified HTML in {summary}");
+        }
+        if config.output.precompress {
+            let reports = output::precompress(&config, &config.prerender.output_dir)
+                .context("Failed to precompress prerendered pages")?;
+            if cli.verbose {
+                println!("🗜️  Precompressed {} file(s)", reports.len());
+            }
+        }
+        return Ok(());
+    }
+    
+    // Initialize syntax set for code highlighting
+    let syntax_set = Arc::new(SyntaxSet::load_defaults_newlines());
+    
+    let assets = AssetManifest::load(&config);
+
+    if let Some(Command::Export { format: ExportFormat::Epub { collection, series, tag, slugs, title, output } }) = &cli.command {
+        let builds = config
+            .collections()
+            .into_iter()
+            .filter(|c| c.name == *collection)
+            .map(|c| load_collection(c, &config, &syntax_set, &assets))
+            .collect::<Result<Vec<_>>>()?;
+        let options = epub::EpubOptions {
+            collection,
+            series: series.as_deref(),
+            tag: tag.as_deref(),
+            slugs,
+            title: title.as_deref(),
+            output,
+        };
+        let chapters = epub::write_epub(&config, &builds, &options).context("Failed to export EPUB")?;
+        println!("📚 Exported {chapters} post(s) to {}", output.display());
+        return Ok(());
+    }
+
+    // Posts that are up to date link images written with the settings of the last build
+    let image_settings = images::settings_hash(&config)?;
+    if !cli.force && images::settings_changed(&config, &image_settings) {
+        if cli.verbose {
+            println!("🖼️  Image settings changed - processing all files");
+        }
+        cli.force = true;
+    }
+    let card_style = og_image::style_hash(&config)?;
+    if !cli.force && og_image::style_changed(&config, &card_style) {
+        if cli.verbose {
+            println!("🖼️  Open Graph card settings changed - processing all files");
+        }
+        cli.force = true;
+    }
+
+    let mut builds = Vec::new();
+    for collection in config.collections() {
+        builds.push(build_collection(collection, &config, &cli, &syntax_set, &assets)?);
+    }
+    images::record_settings(&config, &image_settings).context("Failed to record image settings")?;
+    og_image::record_style(&config, &card_style).context("Failed to record Open Graph card style")?;
+
+    let feed_count = feeds::write_feeds(&config, &builds).context("Failed to write feeds")?;
+    if cli.verbose && feed_count > 0 {
+        println!("📡 Wrote {feed_count} RSS/Atom feeds to {}", config.public_dir.display());
+    }
+    let json_feed_pages = json_feed::write_json_feed(&config, &builds).context("Failed to write JSON Feed")?;
+    if cli.verbose && json_feed_pages > 0 {
+        println!("📡 Wrote JSON Feed with {json_feed_pages} page(s)");
+    }
+
+    if sitemap::write_sitemap(&config, &builds).context("Failed to write sitemap")? && cli.verbose {
+        println!("🗺️  Wrote sitemap.xml");
+    }
+    if sitemap::write_robots(&config).context("Failed to write robots.txt")? && cli.verbose {
+        println!("🤖 Wrote robots.txt");
+    }
+
+    let og_images = og_image::write_og_images(&config, &assets, &builds).context("Failed to write Open Graph images")?;
+    if cli.verbose && og_images > 0 {
+        println!("🖼️  Rendered {og_images} Open Graph image(s)");
+    }
+    // After the cards, whose hashed files only exist once they are drawn
+    if let Some(entries) = assets.write(&config).context("Failed to write asset manifest")? {
+        if cli.verbose {
+            println!("🔖 Wrote asset manifest with {entries} entries");
+        }
+    }
+
+    let schemas = schema::write_schemas(&config).context("Failed to write JSON Schemas")?;
+    if cli.verbose {
+        println!("📐 Wrote {schemas} JSON Schema(s)");
+    }
+
+    let llms_posts = llms::write_llms_txt(&config, &builds).context("Failed to write llms.txt")?;
+    if cli.verbose && llms_posts > 0 {
+        println!("🧾 Wrote llms.txt and llms-full.txt with {llms_posts} post(s)");
+    }
+
+    let search_indexes = search::write_search_indexes(&config, &builds).context("Failed to write search index")?;
+    if cli.verbose && search_indexes > 0 {
+        println!("🔍 Wrote {search_indexes} search index(es)");
+    }
+
+    if let Some(summary) = output::COMPACT_JSON.summary() {
+        println!("🧹 Compacted JSON in {summary}");
+    }
+    if let Some(summary) = output::MINIFIED_HTML.summary() {
+        println!("🧹 Minified HTML in {summary}");
+    }
+
+    let reports = output::precompress(&config, &config.public_dir).context("Failed to precompress output")?;
+    if !reports.is_empty() {
+        if cli.verbose {
+            for report in &reports {
+                println!("   {report}");
+            }
+        }
+        let original: u64 = reports.iter().map(|r| r.original).sum();
+        let gzip: u64 = reports.iter().map(|r| r.gzip).sum();
+        let brotli: u64 = reports.iter().map(|r| r.brotli).sum();
+        println!(
+            "🗜️  Precompressed {} file(s): {} → gzip {} ({}), brotli {} ({})",
+            reports.len(),
+            output::format_size(original),
+            output::format_size(gzip),
+            output::savings(original, gzip),
+            output::format_size(brotli),
+            output::savings(original, brotli)
+        );
+    }
+
+    if builds.len() > 1 {
+        let counts: Vec<String> = builds
+            .iter()
+            .map(|b| format!("{} ({})", b.collection.name, b.posts.len()))
+            .collect();
+        let processed: usize = builds.iter().map(|b| b.processed).sum();
+        let skipped: usize = builds.iter().map(|b| b.skipped).sum();
+        println!("✨ Built {} collections: {} ({processed} processed, {skipped} skipped)", builds.len(), counts.join(", "));
+    }
+
+    let error_count: usize = builds.iter().map(|b| b.errors.len()).sum();
+    if error_count > 0 {
+        eprintln!("⚠️  Build completed with {error_count} errors");
         // Exit with error code if there were processing errors
         std::process::exit(1);
     }
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_tags() {
+        for tag in ["en", "nb-NO", "zh-Hant-TW", "ast"] {
+            assert!(is_language_tag(tag), "{tag}");
+        }
+        for tag in ["", "e", "english", "en_US", "en-", "../en", "en/../../x"] {
+            assert!(!is_language_tag(tag), "{tag}");
+        }
+    }
+
+    #[test]
+    fn test_bundles_are_addressed_by_their_directory() {
+        let dir = std::env::temp_dir().join(format!("site-builder-tasks-{}", std::process::id()));
+        let content_dir = dir.join("content");
+        for (path, text) in [
+            ("intro.md", "# Intro"),
+            ("guides/_index.md", "# Guides"),
+            ("guides/setup/index.md", "# Setup"),
+            ("guides/setup/snippet.md", "Included"),
+            ("trip/index.md", "# Trip"),
+            ("trip/day-two/index.md", "# Part of the trip bundle"),
+        ] {
+            let path = content_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        let config = Config {
+            content_dir: content_dir.clone(),
+            output_dir: dir.join("out"),
+            ..Default::default()
+        };
+
+        let mut tasks = collect_processing_tasks(&config.collections()[0]).unwrap();
+        tasks.sort_by(|a, b| a.slug.cmp(&b.slug));
+        let found: Vec<(&str, &str, Option<&Path>)> = tasks
+            .iter()
+            .map(|t| (t.slug.as_str(), t.section.as_str(), t.bundle_dir.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("guides/setup", "guides", Some(content_dir.join("guides/setup").as_path())),
+                ("intro", "", None),
+                ("trip", "", Some(content_dir.join("trip").as_path())),
+            ]
+        );
+        assert_eq!(tasks[0].output_path, dir.join("out/guides/setup.json"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_exports_leave_the_site_output_alone() {
+        let dir = std::env::temp_dir().join(format!("site-builder-export-{}", std::process::id()));
+        let content_dir = dir.join("content");
+        fs::create_dir_all(&content_dir).unwrap();
+        fs::write(content_dir.join("intro.md"), "---\ntitle: Intro\n---\n\nHello <span class=\"x\">there</span>").unwrap();
+        let mut config = Config {
+            content_dir,
+            output_dir: dir.join("out"),
+            public_dir: dir.join("public"),
+            ..Default::default()
+        };
+        let mut cli = Cli::parse_from(["site-builder", "--production", "export", "epub"]);
+        apply_cli_overrides(&mut cli, &mut config);
+        assert!(cli.force && config.output.compact_json);
+        assert!(!config.output.minify_html && !config.llms.enabled && !config.cite.enabled);
+
+        let build = load_collection(
+            config.collections().remove(0),
+            &config,
+            &SyntaxSet::load_defaults_newlines(),
+            &AssetManifest::default(),
+        )
+        .unwrap();
+        assert_eq!(build.posts[0].title, "Intro");
+        assert!(build.posts[0].content.contains(r#"<span class="x">"#));
+        assert!(!dir.join("out").exists() && !dir.join("public").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,473 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use image::{imageops, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::assets::AssetManifest;
+use crate::config::Config;
+use crate::sections::path_to_slug;
+use crate::CollectionBuild;
+
+pub const OG_IMAGE_WIDTH: u32 = 1200;
+pub const OG_IMAGE_HEIGHT: u32 = 630;
+
+/// Bumped whenever the card layout changes, so existing cards are redrawn
+const LAYOUT_VERSION: u32 = 1;
+
+const PADDING: f32 = 80.0;
+const ACCENT_BAR_WIDTH: u32 = 16;
+const MAX_TITLE_LINES: usize = 3;
+
+/// Everything on a card that is not taken from the post, and which posts share one
+#[derive(Serialize)]
+struct CardStyle<'a> {
+    layout: u32,
+    enabled: bool,
+    prefer_over_cover: bool,
+    site: &'a str,
+    background: Option<String>,
+    fonts: [String; 2],
+    colors: [&'a str; 3],
+}
+
+/// Everything that ends up on a card. A card is only redrawn when this changes.
+#[derive(Serialize)]
+struct CardInputs<'a> {
+    style: &'a str,
+    title: &'a str,
+    tags: &'a [String],
+    date: String,
+}
+
+struct Card<'a> {
+    path: PathBuf,
+    title: &'a str,
+    tags: &'a [String],
+    date: String,
+}
+
+/// Path and modification time of a file the cards are drawn with
+fn file_stamp(path: &Path) -> String {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    format!("{}@{modified:?}", path.display())
+}
+
+/// Whether a post's share image is its card: without a cover, or always with `prefer_over_cover`
+pub fn shares_card(config: &Config, has_cover: bool) -> bool {
+    let settings = &config.og_images;
+    settings.enabled && (settings.prefer_over_cover || !has_cover)
+}
+
+/// Hash of the card style. Posts link their card and fingerprinted card URLs are named after
+/// the style, so posts are processed again when it changes.
+pub fn style_hash(config: &Config) -> Result<String> {
+    let settings = &config.og_images;
+    let style = serde_json::to_string(&CardStyle {
+        layout: LAYOUT_VERSION,
+        enabled: settings.enabled,
+        prefer_over_cover: settings.prefer_over_cover,
+        site: &config.site.title,
+        background: settings.background.as_deref().map(file_stamp),
+        fonts: [file_stamp(&settings.font), file_stamp(&settings.bold_font)],
+        colors: [
+            settings.background_color.as_str(),
+            settings.accent_color.as_str(),
+            settings.text_color.as_str(),
+        ],
+    })?;
+    Ok(format!("{:x}", Sha256::digest(style)))
+}
+
+/// Where the `style_hash` of the last build is kept
+fn style_path(config: &Config) -> PathBuf {
+    config.cache.directory.join("og-images.style.sha256")
+}
+
+/// Whether posts were built with another card style than `hash`
+pub fn style_changed(config: &Config, hash: &str) -> bool {
+    fs::read_to_string(style_path(config)).map_or(true, |last| last.trim() != hash)
+}
+
+pub fn record_style(config: &Config, hash: &str) -> Result<()> {
+    let path = style_path(config);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create cache directory: {}", parent.display()))?;
+    }
+    fs::write(&path, hash).with_context(|| format!("Failed to write Open Graph card style: {}", path.display()))
+}
+
+/// The inputs of a post's card as JSON, which also names the card when fingerprinting is on
+pub fn card_inputs(style: &str, title: &str, tags: &[String], date: &DateTime<Utc>) -> Result<String> {
+    Ok(serde_json::to_string(&CardInputs {
+        style,
+        title,
+        tags,
+        date: card_date(date),
+    })?)
+}
+
+/// ISO dates read the same in every language a post can be written in
+fn card_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Public URL of the generated preview card for a post, content-hashed after its inputs
+pub fn og_image_url(config: &Config, assets: &AssetManifest, collection: &str, slug: &str, inputs: &str) -> Result<String> {
+    let url = format!("/{}/{collection}/{slug}.png", path_to_slug(&config.og_images.output_dir));
+    assets.fingerprint_inputs(config, &url, inputs.as_bytes())
+}
+
+/// Render a preview card for every post that shares one and whose title, tags, date or style
+/// changed, and delete the cards no post shares any more. Returns the number of cards drawn.
+pub fn write_og_images(config: &Config, assets: &AssetManifest, builds: &[CollectionBuild]) -> Result<usize> {
+    let settings = &config.og_images;
+    if !settings.enabled {
+        return Ok(0);
+    }
+
+    let colors = [
+        parse_color(&settings.background_color)?,
+        parse_color(&settings.accent_color)?,
+        parse_color(&settings.text_color)?,
+    ];
+    let background = settings
+        .background
+        .as_deref()
+        .map(|path| {
+            image::open(path)
+                .with_context(|| format!("Failed to open Open Graph template: {}", path.display()))
+                .map(|img| {
+                    img.resize_to_fill(OG_IMAGE_WIDTH, OG_IMAGE_HEIGHT, imageops::FilterType::Lanczos3)
+                        .to_rgba8()
+                })
+        })
+        .transpose()?;
+    let style = style_hash(config)?;
+
+    let manifest_path = config.cache.directory.join("og-images.json");
+    let manifest: BTreeMap<String, String> = if config.cache.enabled {
+        fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    } else {
+        BTreeMap::new()
+    };
+
+    let mut cards = Vec::new();
+    let mut shared = BTreeMap::new();
+    for build in builds {
+        for post in build.posts.iter().filter(|post| shares_card(config, post.cover.is_some())) {
+            let inputs = card_inputs(&style, &post.title, &post.tags, &post.date)?;
+            let url = og_image_url(config, assets, &build.collection.name, &post.slug, &inputs)?;
+            let path = config.public_dir.join(url.trim_start_matches('/'));
+
+            let unchanged = path.exists() && manifest.get(&url) == Some(&inputs);
+            shared.insert(url, inputs);
+            if unchanged {
+                continue;
+            }
+            cards.push(Card {
+                path,
+                title: &post.title,
+                tags: &post.tags,
+                date: card_date(&post.date),
+            });
+        }
+    }
+    remove_unshared_cards(config, &shared)?;
+
+    if !cards.is_empty() {
+        let regular_font = read_font(&settings.font)?;
+        let bold_font = read_font(&settings.bold_font)?;
+        let regular = FontRef::try_from_slice(&regular_font)
+            .with_context(|| format!("Failed to load font: {}", settings.font.display()))?;
+        let bold = FontRef::try_from_slice(&bold_font)
+            .with_context(|| format!("Failed to load font: {}", settings.bold_font.display()))?;
+        draw_cards(&cards, config, background.as_ref(), colors, &regular, &bold)?;
+    }
+
+    if config.cache.enabled && shared != manifest {
+        fs::create_dir_all(&config.cache.directory).with_context(|| {
+            format!("Failed to create cache directory: {}", config.cache.directory.display())
+        })?;
+        fs::write(&manifest_path, serde_json::to_string_pretty(&shared)?)
+            .with_context(|| format!("Failed to write Open Graph image cache: {}", manifest_path.display()))?;
+    }
+
+    Ok(cards.len())
+}
+
+fn read_font(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("Failed to read Open Graph card font: {}", path.display()))
+}
+
+/// Delete the cards below the card directory that no post shares, e.g. of deleted posts
+fn remove_unshared_cards(config: &Config, shared: &BTreeMap<String, String>) -> Result<()> {
+    let dir = config.public_dir.join(&config.og_images.output_dir);
+    let shared: Vec<PathBuf> = shared
+        .keys()
+        .map(|url| config.public_dir.join(url.trim_start_matches('/')))
+        .collect();
+    for entry in WalkDir::new(&dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().and_then(|ext| ext.to_str()) == Some("png"))
+    {
+        if !shared.iter().any(|path| path == entry.path()) {
+            fs::remove_file(entry.path())
+                .with_context(|| format!("Failed to remove Open Graph image: {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn draw_cards(
+    cards: &[Card],
+    config: &Config,
+    background: Option<&RgbaImage>,
+    colors: [Rgba<u8>; 3],
+    regular: &FontRef,
+    bold: &FontRef,
+) -> Result<()> {
+
+    cards.par_iter().try_for_each(|card| -> Result<()> {
+        let canvas = render_card(card, config, background, colors, regular, bold);
+        if let Some(parent) = card.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create Open Graph image directory: {}", parent.display()))?;
+        }
+        canvas
+            .save_with_format(&card.path, image::ImageFormat::Png)
+            .with_context(|| format!("Failed to write Open Graph image: {}", card.path.display()))
+    })
+}
+
+fn render_card(
+    card: &Card,
+    config: &Config,
+    background: Option<&RgbaImage>,
+    [background_color, accent, text]: [Rgba<u8>; 3],
+    regular: &FontRef,
+    bold: &FontRef,
+) -> RgbaImage {
+    let mut canvas = match background {
+        Some(template) => template.clone(),
+        None => RgbaImage::from_pixel(OG_IMAGE_WIDTH, OG_IMAGE_HEIGHT, background_color),
+    };
+    for y in 0..OG_IMAGE_HEIGHT {
+        for x in 0..ACCENT_BAR_WIDTH {
+            canvas.put_pixel(x, y, accent);
+        }
+    }
+
+    let max_width = OG_IMAGE_WIDTH as f32 - 2.0 * PADDING;
+    let muted = Rgba([text[0], text[1], text[2], 200]);
+
+    draw_text(&mut canvas, regular, 30.0, PADDING, PADDING + 30.0, &card.date, muted);
+
+    // Shrink the title until it fits, then cut off whatever still overflows
+    let (size, lines) = [72.0, 64.0, 56.0, 48.0]
+        .iter()
+        .map(|&size| (size, wrap_text(bold, size, card.title, max_width)))
+        .find(|(_, lines)| lines.len() <= MAX_TITLE_LINES)
+        .unwrap_or_else(|| {
+            let mut lines = wrap_text(bold, 48.0, card.title, max_width);
+            lines.truncate(MAX_TITLE_LINES);
+            if let Some(last) = lines.last_mut() {
+                *last = ellipsize(bold, 48.0, last, max_width);
+            }
+            (48.0, lines)
+        });
+    let mut baseline = PADDING + 60.0 + size * 1.1;
+    for line in &lines {
+        draw_text(&mut canvas, bold, size, PADDING, baseline, line, text);
+        baseline += size * 1.2;
+    }
+
+    if !card.tags.is_empty() {
+        let tags = card.tags.iter().map(|tag| format!("#{tag}")).collect::<Vec<_>>().join("   ");
+        let tags = ellipsize(bold, 30.0, &tags, max_width);
+        draw_text(&mut canvas, bold, 30.0, PADDING, baseline + 16.0, &tags, accent);
+    }
+
+    let footer = OG_IMAGE_HEIGHT as f32 - PADDING + 10.0;
+    draw_text(&mut canvas, regular, 32.0, PADDING, footer, &config.site.title, text);
+
+    canvas
+}
+
+fn text_width(font: &FontRef, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Greedy word wrap. Words wider than a line are left to overflow.
+fn wrap_text(font: &FontRef, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{line} {word}") };
+        if line.is_empty() || text_width(font, size, &candidate) <= max_width {
+            line = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn ellipsize(font: &FontRef, size: f32, text: &str, max_width: f32) -> String {
+    if text_width(font, size, text) <= max_width {
+        return text.to_string();
+    }
+    let mut shortened = text.to_string();
+    while !shortened.is_empty() && text_width(font, size, &format!("{shortened}…")) > max_width {
+        shortened.pop();
+    }
+    format!("{}…", shortened.trim_end())
+}
+
+/// Draw a single line of text with its baseline at `y`, alpha-blended onto the canvas
+fn draw_text(canvas: &mut RgbaImage, font: &FontRef, size: f32, x: f32, y: f32, text: &str, color: Rgba<u8>) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut caret = x;
+    let mut previous = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(size, point(caret, y));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= canvas.width() as i32 || py >= canvas.height() as i32 {
+                return;
+            }
+            let alpha = coverage * color[3] as f32 / 255.0;
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            for channel in 0..3 {
+                pixel[channel] = (color[channel] as f32 * alpha + pixel[channel] as f32 * (1.0 - alpha)).round() as u8;
+            }
+            pixel[3] = 255;
+        });
+    }
+}
+
+/// Parse `#RRGGBB` or `#RRGGBBAA`
+fn parse_color(value: &str) -> Result<Rgba<u8>> {
+    let hex = value.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .with_context(|| format!("Invalid color `{value}`, expected #RRGGBB"))
+    };
+    match hex.len() {
+        6 => Ok(Rgba([channel(0)?, channel(2)?, channel(4)?, 255])),
+        8 => Ok(Rgba([channel(0)?, channel(2)?, channel(4)?, channel(6)?])),
+        _ => anyhow::bail!("Invalid color `{value}`, expected #RRGGBB"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Post;
+
+    #[test]
+    fn test_title_wrapping_and_colors() {
+        let bold_font = read_font(&Config::default().og_images.bold_font).unwrap();
+        let bold = FontRef::try_from_slice(&bold_font).unwrap();
+        let lines = wrap_text(&bold, 72.0, "The AI Revolution: Understanding Hype, Reality, and the Path Forward", 1040.0);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| text_width(&bold, 72.0, line) <= 1040.0));
+
+        let short = ellipsize(&bold, 30.0, &"#tag ".repeat(100), 400.0);
+        assert!(short.ends_with('…') && text_width(&bold, 30.0, &short) <= 400.0);
+
+        assert_eq!(parse_color("#07485B").unwrap(), Rgba([7, 72, 91, 255]));
+        assert!(parse_color("teal").is_err());
+    }
+
+    #[test]
+    fn test_cards_follow_the_posts_without_a_cover() {
+        let dir = std::env::temp_dir().join(format!("site-builder-og-{}", std::process::id()));
+        let mut config = Config {
+            public_dir: dir.join("public"),
+            ..Default::default()
+        };
+        config.cache.directory = dir.join("cache");
+        let post = |slug: &str| Post {
+            slug: slug.to_string(),
+            title: format!("About {slug}"),
+            ..Default::default()
+        };
+        let covered = Post {
+            cover: Some(
+                serde_json::from_value(serde_json::json!({
+                    "original": "/images/blog/covered-photo.jpg",
+                    "original_size": { "width": 1, "height": 1, "aspect_ratio": 1.0 },
+                    "cover": "/images/blog/covered-photo-cover.jpg",
+                    "thumbnail": "/images/blog/covered-photo-thumbnail.jpg",
+                    "srcset": "",
+                    "variants": [],
+                }))
+                .unwrap(),
+            ),
+            ..post("covered")
+        };
+        let build = |posts: Vec<Post>| {
+            [CollectionBuild {
+                collection: config.collections().remove(0),
+                posts,
+                processed: 0,
+                skipped: 0,
+                errors: Vec::new(),
+            }]
+        };
+        let card = |slug: &str| config.public_dir.join(format!("images/og/blog/{slug}.png"));
+        let assets = AssetManifest::default();
+
+        assert_eq!(write_og_images(&config, &assets, &build(vec![post("plain"), covered])).unwrap(), 1);
+        assert!(card("plain").exists());
+        assert!(!card("covered").exists(), "posts with a cover share the cover");
+        assert_eq!(write_og_images(&config, &assets, &build(vec![post("plain")])).unwrap(), 0);
+        assert_eq!(write_og_images(&config, &assets, &build(Vec::new())).unwrap(), 0);
+        assert!(!card("plain").exists(), "cards of deleted posts are removed");
+
+        let date = "2024-03-20T12:00:00Z".parse().unwrap();
+        assert!(card_inputs("style", "Hei", &[], &date).unwrap().contains(r#""date":"2024-03-20""#));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::should_process_file;
+
+/// Brotli quality and window size. Files are compressed once per change, so use the strongest setting.
+const BROTLI_QUALITY: u32 = 11;
+const BROTLI_WINDOW: u32 = 22;
+
+/// Size of one precompressed file and its `.gz` and `.br` siblings
+pub struct SizeReport {
+    /// Path relative to `public_dir`
+    pub path: PathBuf,
+    pub original: u64,
+    pub gzip: u64,
+    pub brotli: u64,
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} → gzip {} ({}), brotli {} ({})",
+            self.path.display(),
+            format_size(self.original),
+            format_size(self.gzip),
+            savings(self.original, self.gzip),
+            format_size(self.brotli),
+            savings(self.original, self.brotli)
+        )
+    }
+}
+
+/// Sizes before and after compacting or minifying, summed over every file of one kind
+pub struct SizeTotals {
+    files: AtomicU64,
+    original: AtomicU64,
+    optimized: AtomicU64,
+}
+
+impl SizeTotals {
+    const fn new() -> Self {
+        SizeTotals {
+            files: AtomicU64::new(0),
+            original: AtomicU64::new(0),
+            optimized: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, original: usize, optimized: usize) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.original.fetch_add(original as u64, Ordering::Relaxed);
+        self.optimized.fetch_add(optimized as u64, Ordering::Relaxed);
+    }
+
+    /// `12 file(s): 40.1 KB → 31.7 KB (-21%)`, or `None` if nothing was recorded
+    pub fn summary(&self) -> Option<String> {
+        let files = self.files.load(Ordering::Relaxed);
+        let original = self.original.load(Ordering::Relaxed);
+        let optimized = self.optimized.load(Ordering::Relaxed);
+        (files > 0).then(|| {
+            format!(
+                "{files} file(s): {} → {} ({})",
+                format_size(original),
+                format_size(optimized),
+                savings(original, optimized)
+            )
+        })
+    }
+}
+
+/// Pretty-printed vs compact size of every JSON file written with `output.compact_json`
+pub static COMPACT_JSON: SizeTotals = SizeTotals::new();
+/// Original vs minified size of every HTML string minified with `output.minify_html`
+pub static MINIFIED_HTML: SizeTotals = SizeTotals::new();
+
+/// Counts the bytes written to it
+#[derive(Default)]
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serialize JSON without indentation when `output.compact_json` is set
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(config: &Config, mut writer: W, value: &T) -> serde_json::Result<()> {
+    if !config.output.compact_json {
+        return serde_json::to_writer_pretty(writer, value);
+    }
+    let mut pretty = ByteCounter::default();
+    serde_json::to_writer_pretty(&mut pretty, value)?;
+    let compact = serde_json::to_vec(value)?;
+    COMPACT_JSON.record(pretty.0, compact.len());
+    writer.write_all(&compact).map_err(serde_json::Error::io)
+}
+
+/// Minify HTML when `output.minify_html` is set. The output stays spec compliant, and closing
+/// tags are kept because post content is inserted into other pages by the app.
+pub fn minify_html(config: &Config, html: String) -> String {
+    if !config.output.minify_html {
+        return html;
+    }
+    let cfg = minify_html::Cfg {
+        keep_closing_tags: true,
+        minify_css: true,
+        ..minify_html::Cfg::spec_compliant()
+    };
+    match String::from_utf8(minify_html::minify(html.as_bytes(), &cfg)) {
+        Ok(minified) => {
+            MINIFIED_HTML.record(html.len(), minified.len());
+            minified
+        }
+        Err(_) => html,
+    }
+}
+
+/// Write `.gz` and `.br` siblings for files below `dir` with one of the configured
+/// extensions. Only files changed since their last compression are compressed again.
+/// Returns the sizes of every matching file, sorted by path.
+pub fn precompress(config: &Config, dir: &Path) -> Result<Vec<SizeReport>> {
+    let settings = &config.output;
+    if !settings.precompress {
+        return Ok(Vec::new());
+    }
+
+    let files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| settings.precompress_extensions.iter().any(|x| x == ext))
+        })
+        .map(|e| e.into_path())
+        .collect();
+
+    let mut reports: Vec<SizeReport> = files
+        .par_iter()
+        .map(|path| compress_file(config, path))
+        .collect::<Result<_>>()?;
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(reports)
+}
+
+fn compress_file(config: &Config, path: &Path) -> Result<SizeReport> {
+    let content = fs::read(path).with_context(|| format!("Failed to read file to compress: {}", path.display()))?;
+
+    let gzip_path = sibling(path, "gz");
+    if should_process_file(path, &gzip_path, false)? {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&content)?;
+        fs::write(&gzip_path, encoder.finish()?)
+            .with_context(|| format!("Failed to write {}", gzip_path.display()))?;
+    }
+
+    let brotli_path = sibling(path, "br");
+    if should_process_file(path, &brotli_path, false)? {
+        let mut compressed = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+            writer.write_all(&content)?;
+        }
+        fs::write(&brotli_path, compressed)
+            .with_context(|| format!("Failed to write {}", brotli_path.display()))?;
+    }
+
+    Ok(SizeReport {
+        path: path.strip_prefix(&config.public_dir).unwrap_or(path).to_path_buf(),
+        original: content.len() as u64,
+        gzip: fs::metadata(&gzip_path)?.len(),
+        brotli: fs::metadata(&brotli_path)?.len(),
+    })
+}
+
+/// `feed.json` → `feed.json.gz`
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+pub fn savings(original: u64, compressed: u64) -> String {
+    if original == 0 {
+        return "0%".to_string();
+    }
+    format!("{:+.0}%", (compressed as f64 - original as f64) / original as f64 * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minified_content_keeps_code_whitespace() {
+        let mut config = Config::default();
+        config.output.minify_html = true;
+        let html = "<p>Hello   <strong>world</strong> !</p>\n\n<pre><code>fn main() {\n    x();\n}</code></pre>\n";
+        let minified = minify_html(&config, html.to_string());
+        assert!(minified.len() < html.len());
+        assert!(minified.contains("<strong>world</strong> !</p>"));
+        assert!(minified.contains("fn main() {\n    x();\n}"));
+        assert_eq!(savings(1000, 250), "-75%");
+        assert_eq!(sibling(Path::new("public/feed.json"), "br"), PathBuf::from("public/feed.json.br"));
+    }
+
+    #[test]
+    fn test_compact_json_sizes_are_recorded() {
+        let mut config = Config::default();
+        config.output.compact_json = true;
+        let value = serde_json::json!({ "title": "Hello", "tags": ["a", "b"] });
+        let mut written = Vec::new();
+        to_writer(&config, &mut written, &value).unwrap();
+        assert_eq!(written, serde_json::to_vec(&value).unwrap());
+
+        let totals = SizeTotals::new();
+        assert_eq!(totals.summary(), None);
+        totals.record(2048, 1024);
+        totals.record(2048, 1024);
+        assert_eq!(totals.summary().unwrap(), "2 file(s): 4.0 KB → 2.0 KB (-50%)");
+    }
+}
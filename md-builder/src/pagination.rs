@@ -0,0 +1,346 @@
+use anyhow::{Context, Result};
+use chrono::Datelike;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+use walkdir::WalkDir;
+
+use crate::config::{CollectionSettings, Config};
+use crate::output;
+use crate::schema::{self, SCHEMA_VERSION};
+use crate::PostSummary;
+
+/// Directory below `<public_dir>/<collection>/` holding the chunks and the manifest. Posts and
+/// sections of that name would share it, so the slug is reserved while pagination is enabled.
+const INDEX_DIR: &str = "index";
+
+/// Entry point for lazy loading: counts and the URL of every chunk
+#[derive(Serialize, TS, JsonSchema)]
+pub struct IndexManifest {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    schema_version: u32,
+    total_posts: usize,
+    page_size: usize,
+    total_pages: usize,
+    pages: Vec<PageLink>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    archives: Vec<YearArchive>,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+pub struct PageLink {
+    page: usize,
+    url: String,
+    count: usize,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+pub struct YearArchive {
+    year: i32,
+    url: String,
+    count: usize,
+    months: Vec<MonthArchive>,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+pub struct MonthArchive {
+    month: u32,
+    url: String,
+    count: usize,
+}
+
+/// One page of summaries in index order
+#[derive(Serialize, TS, JsonSchema)]
+pub struct IndexPage<'a> {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    schema_version: u32,
+    page: usize,
+    total_pages: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    prev: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    next: Option<String>,
+    posts: &'a [PostSummary],
+}
+
+/// Posts of one year or month, newest first
+#[derive(Serialize, TS, JsonSchema)]
+pub struct ArchiveChunk<'a> {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    schema_version: u32,
+    year: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    month: Option<u32>,
+    posts: Vec<&'a PostSummary>,
+}
+
+/// Write `index/page-N.json` chunks, optional `index/archive/YYYY[-MM].json` chunks and
+/// `index/manifest.json` to `<public_dir>/<collection>/`, where the app fetches them. Chunk URLs
+/// are site paths. Chunks of an earlier build that were not written again are removed.
+/// Returns the number of pages written.
+pub fn write_index_pages(
+    config: &Config,
+    collection: &CollectionSettings,
+    summaries: &[PostSummary],
+) -> Result<usize> {
+    let settings = &config.pagination;
+    if !settings.enabled {
+        return Ok(0);
+    }
+    if let Some(post) = summaries.iter().find(|post| post.slug.split('/').next() == Some(INDEX_DIR)) {
+        anyhow::bail!(
+            "The slug `{}` in {} is reserved for the paginated index, rename `{INDEX_DIR}` or disable `pagination`",
+            post.slug,
+            collection.name
+        );
+    }
+
+    let index_dir = config.public_dir.join(&collection.name).join(INDEX_DIR);
+    let mut written = Vec::new();
+    let page_size = settings.page_size.max(1);
+    let chunks: Vec<&[PostSummary]> = if summaries.is_empty() {
+        vec![&[]]
+    } else {
+        summaries.chunks(page_size).collect()
+    };
+    let total_pages = chunks.len();
+
+    let mut pages = Vec::with_capacity(total_pages);
+    for (i, posts) in chunks.iter().enumerate() {
+        let page = i + 1;
+        let chunk = IndexPage {
+            schema_version: SCHEMA_VERSION,
+            page,
+            total_pages,
+            prev: (page > 1).then(|| page_url(collection, page - 1)),
+            next: (page < total_pages).then(|| page_url(collection, page + 1)),
+            posts,
+        };
+        written.push(write_json(config, &page_url(collection, page), &chunk)?);
+        pages.push(PageLink {
+            page,
+            url: page_url(collection, page),
+            count: posts.len(),
+        });
+    }
+
+    let archives = if settings.archives {
+        write_archives(config, collection, summaries, &mut written)?
+    } else {
+        Vec::new()
+    };
+
+    let manifest = IndexManifest {
+        schema_version: SCHEMA_VERSION,
+        total_posts: summaries.len(),
+        page_size,
+        total_pages,
+        pages,
+        archives,
+    };
+    written.push(write_json(config, &chunk_url(collection, "manifest.json"), &manifest)?);
+    remove_stale_chunks(&index_dir, &written)?;
+
+    Ok(total_pages)
+}
+
+fn write_archives(
+    config: &Config,
+    collection: &CollectionSettings,
+    summaries: &[PostSummary],
+    written: &mut Vec<PathBuf>,
+) -> Result<Vec<YearArchive>> {
+    let mut by_month: BTreeMap<(i32, u32), Vec<&PostSummary>> = BTreeMap::new();
+    for summary in summaries {
+        by_month
+            .entry((summary.date.year(), summary.date.month()))
+            .or_default()
+            .push(summary);
+    }
+
+    let mut years: Vec<YearArchive> = Vec::new();
+    // Newest year and month first, like the default index order
+    for ((year, month), mut posts) in by_month.into_iter().rev() {
+        posts.sort_by_key(|post| std::cmp::Reverse(post.date));
+        let url = chunk_url(collection, &format!("archive/{year}-{month:02}.json"));
+        let count = posts.len();
+        let chunk = ArchiveChunk {
+            schema_version: SCHEMA_VERSION,
+            year,
+            month: Some(month),
+            posts,
+        };
+        written.push(write_json(config, &url, &chunk)?);
+
+        if years.last().is_none_or(|y| y.year != year) {
+            years.push(YearArchive {
+                year,
+                url: chunk_url(collection, &format!("archive/{year}.json")),
+                count: 0,
+                months: Vec::new(),
+            });
+        }
+        let archive = years.last_mut().expect("year archive was just pushed");
+        archive.count += count;
+        archive.months.push(MonthArchive { month, url, count });
+    }
+
+    for archive in &years {
+        let mut posts: Vec<&PostSummary> = summaries
+            .iter()
+            .filter(|post| post.date.year() == archive.year)
+            .collect();
+        posts.sort_by_key(|post| std::cmp::Reverse(post.date));
+        let chunk = ArchiveChunk {
+            schema_version: SCHEMA_VERSION,
+            year: archive.year,
+            month: None,
+            posts,
+        };
+        written.push(write_json(config, &archive.url, &chunk)?);
+    }
+
+    Ok(years)
+}
+
+/// Site path of a file in the collection's index directory, e.g. `/blog/index/manifest.json`
+fn chunk_url(collection: &CollectionSettings, path: &str) -> String {
+    format!("/{}/{INDEX_DIR}/{path}", collection.name)
+}
+
+fn page_url(collection: &CollectionSettings, page: usize) -> String {
+    chunk_url(collection, &format!("page-{page}.json"))
+}
+
+/// Remove JSON files below `index_dir` that are not in `written`, and directories left empty.
+/// Anything else in it is left alone.
+fn remove_stale_chunks(index_dir: &Path, written: &[PathBuf]) -> Result<()> {
+    for entry in WalkDir::new(index_dir).contents_first(true).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            if path != index_dir && fs::read_dir(path)?.next().is_none() {
+                fs::remove_dir(path)
+                    .with_context(|| format!("Failed to remove index directory: {}", path.display()))?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "json") && !written.iter().any(|w| w == path) {
+            fs::remove_file(path).with_context(|| format!("Failed to remove stale index chunk: {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a chunk to the file behind its site path and return that file
+fn write_json<T: Serialize + JsonSchema>(config: &Config, url: &str, value: &T) -> Result<PathBuf> {
+    let path = config.public_dir.join(url.trim_start_matches('/'));
+    schema::validate(value, &path)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create index directory: {}", parent.display()))?;
+    }
+    let file = File::create(&path)
+        .with_context(|| format!("Failed to create index chunk: {}", path.display()))?;
+    output::to_writer(config, file, value)
+        .with_context(|| format!("Failed to write index chunk to: {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_pages_archives_and_manifest() {
+        let dir = std::env::temp_dir().join(format!("site-builder-pagination-{}", std::process::id()));
+        let mut config = Config {
+            public_dir: dir.clone(),
+            ..Default::default()
+        };
+        config.pagination.page_size = 2;
+        config.pagination.archives = true;
+        let collection = config.collections().remove(0);
+        let summary = |slug: &str, date: &str| PostSummary {
+            title: slug.to_string(),
+            date: date.parse().unwrap(),
+            slug: slug.to_string(),
+            section: String::new(),
+            language: None,
+            description: None,
+            tags: Vec::new(),
+            cover: None,
+            fields: BTreeMap::new(),
+        };
+        // Index order, which is not date order
+        let summaries = [
+            summary("e", "2024-03-20T12:00:00Z"),
+            summary("d", "2024-03-02T12:00:00Z"),
+            summary("c", "2023-12-24T12:00:00Z"),
+            summary("b", "2024-01-10T12:00:00Z"),
+            summary("a", "2023-12-01T12:00:00Z"),
+        ];
+
+        // Chunks from an earlier, longer build are removed, other files are left alone
+        let index = dir.join("blog/index");
+        fs::create_dir_all(&index).unwrap();
+        fs::write(index.join("page-9.json"), "{}").unwrap();
+        fs::write(index.join("notes.txt"), "kept").unwrap();
+
+        assert_eq!(write_index_pages(&config, &collection, &summaries).unwrap(), 3);
+        assert!(!index.join("page-9.json").exists());
+        assert!(index.join("notes.txt").exists());
+
+        let slugs = |chunk: &serde_json::Value| -> Vec<String> {
+            chunk["posts"].as_array().unwrap().iter().map(|p| p["slug"].as_str().unwrap().to_string()).collect()
+        };
+        let first = read(&index.join("page-1.json"));
+        assert_eq!(slugs(&first), ["e", "d"]);
+        assert_eq!(first["prev"], serde_json::Value::Null);
+        assert_eq!(first["next"], "/blog/index/page-2.json");
+        let second = read(&index.join("page-2.json"));
+        assert_eq!(
+            (second["prev"].as_str(), second["next"].as_str()),
+            (Some("/blog/index/page-1.json"), Some("/blog/index/page-3.json"))
+        );
+        let last = read(&index.join("page-3.json"));
+        assert_eq!(slugs(&last), ["a"]);
+        assert_eq!(last["next"], serde_json::Value::Null);
+
+        // Every URL in the manifest is a file below public_dir
+        let manifest = read(&index.join("manifest.json"));
+        assert_eq!((manifest["total_posts"].as_u64(), manifest["total_pages"].as_u64()), (Some(5), Some(3)));
+        assert_eq!(manifest["pages"][2], serde_json::json!({ "page": 3, "url": "/blog/index/page-3.json", "count": 1 }));
+        for page in manifest["pages"].as_array().unwrap() {
+            assert!(dir.join(page["url"].as_str().unwrap().trim_start_matches('/')).exists());
+        }
+
+        // Archives are newest first, with posts sorted by date inside each chunk
+        let years = manifest["archives"].as_array().unwrap();
+        assert_eq!((years[0]["year"].as_i64(), years[0]["count"].as_u64()), (Some(2024), Some(3)));
+        assert_eq!(years[0]["months"][0]["url"], "/blog/index/archive/2024-03.json");
+        assert_eq!(years[0]["months"][1]["url"], "/blog/index/archive/2024-01.json");
+        assert_eq!(years[1]["year"], 2023);
+        assert_eq!(slugs(&read(&index.join("archive/2023.json"))), ["c", "a"]);
+        assert_eq!(slugs(&read(&index.join("archive/2024-03.json"))), ["e", "d"]);
+
+        // An empty collection still gets one empty page for the app to load
+        assert_eq!(write_index_pages(&config, &collection, &[]).unwrap(), 1);
+        assert_eq!(slugs(&read(&index.join("page-1.json"))), Vec::<String>::new());
+        assert!(!index.join("archive").exists());
+
+        // Posts and sections named like the index directory are rejected
+        let error = write_index_pages(&config, &collection, &[summary("index/setup", "2024-01-01T12:00:00Z")]).unwrap_err();
+        assert!(error.to_string().contains("reserved"));
+        assert!(write_index_pages(&config, &collection, &[summary("indexing", "2024-01-01T12:00:00Z")]).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{Context, Result};
+use html_escape::encode_double_quoted_attribute as attr;
+use minijinja::{context, path_loader, Environment, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, PrerenderSettings};
+use crate::output;
+use crate::seo::SeoMetadata;
+use crate::{post_path, CollectionBuild, Post};
+
+/// A post as seen by templates: every `Post` field plus its path and absolute URL
+#[derive(Serialize)]
+struct PageEntry<'a> {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(flatten)]
+    post: &'a Post,
+}
+
+/// A chunk in Vite's `.vite/manifest.json`, keyed by source path
+#[derive(Deserialize)]
+struct ViteChunk {
+    file: String,
+    #[serde(default)]
+    css: Vec<String>,
+    #[serde(default)]
+    imports: Vec<String>,
+}
+
+/// Built files every page loads, as site paths
+#[derive(Serialize, Debug, PartialEq)]
+struct AppAssets {
+    /// The hashed entry script
+    script: String,
+    /// CSS of the entry and of the chunks it imports
+    stylesheets: Vec<String>,
+    /// Chunks the entry imports, preloaded alongside it
+    preloads: Vec<String>,
+}
+
+/// Look up the app entry and everything it statically imports in the Vite manifest
+fn app_assets(settings: &PrerenderSettings) -> Result<AppAssets> {
+    let path = &settings.vite_manifest;
+    let manifest: HashMap<String, ViteChunk> = fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(serde_json::from_str(&json)?))
+        .with_context(|| format!("Failed to read Vite manifest (run `vite build` first): {}", path.display()))?;
+    let entry = manifest
+        .get(&settings.vite_entry)
+        .with_context(|| format!("No `{}` entry in Vite manifest: {}", settings.vite_entry, path.display()))?;
+
+    let mut assets = AppAssets {
+        script: format!("/{}", entry.file),
+        stylesheets: entry.css.iter().map(|file| format!("/{file}")).collect(),
+        preloads: Vec::new(),
+    };
+    let mut pending: Vec<&String> = entry.imports.iter().collect();
+    let mut seen = vec![&settings.vite_entry];
+    while let Some(key) = pending.pop() {
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+        let Some(chunk) = manifest.get(key) else {
+            continue;
+        };
+        assets.preloads.push(format!("/{}", chunk.file));
+        for file in &chunk.css {
+            let href = format!("/{file}");
+            if !assets.stylesheets.contains(&href) {
+                assets.stylesheets.push(href);
+            }
+        }
+        pending.extend(&chunk.imports);
+    }
+    Ok(assets)
+}
+
+/// Write static HTML for the configured collections into the built app in
+/// `prerender.output_dir`, so crawlers see content without JavaScript. Runs after `vite build`,
+/// whose manifest names the scripts and stylesheets the pages load. Returns the number of pages written.
+pub fn write_pages(config: &Config, builds: &[CollectionBuild]) -> Result<usize> {
+    let settings = &config.prerender;
+    if settings.post_template.is_none() && settings.index_template.is_none() {
+        return Ok(0);
+    }
+
+    let app = app_assets(settings)?;
+    let mut env = Environment::new();
+    env.set_loader(path_loader(&settings.template_dir));
+    let mut written = 0;
+
+    for build in builds
+        .iter()
+        .filter(|b| settings.collections.contains(&b.collection.name))
+    {
+        let collection = &build.collection.name;
+        let entries: Vec<PageEntry> = build
+            .posts
+            .iter()
+            .map(|post| {
+                let path = post_path(collection, &post.slug);
+                PageEntry {
+                    url: config.absolute_url(&path),
+                    path,
+                    post,
+                }
+            })
+            .collect();
+
+        if let Some(name) = &settings.post_template {
+            let template = env
+                .get_template(name)
+                .with_context(|| format!("Failed to load post template: {name}"))?;
+            for entry in &entries {
+                let html = template
+                    .render(context! {
+                        site => &config.site,
+                        app => &app,
+                        collection => collection,
+                        post => entry,
+                        meta_tags => Value::from_safe_string(entry.post.seo.as_ref().map(meta_tags).unwrap_or_default()),
+                        json_ld => Value::from_safe_string(entry.post.json_ld.as_ref().map(script_json).transpose()?.unwrap_or_default()),
+                        post_json => Value::from_safe_string(script_json(entry.post)?),
+                    })
+                    .with_context(|| format!("Failed to render {} with {name}", entry.path))?;
+                write_page(&settings.output_dir.join(entry.path.trim_start_matches('/')), &output::minify_html(config, html))?;
+                written += 1;
+            }
+        }
+
+        if let Some(name) = &settings.index_template {
+            let template = env
+                .get_template(name)
+                .with_context(|| format!("Failed to load index template: {name}"))?;
+            let path = format!("/{collection}");
+            let url = config.absolute_url(&path);
+            let json_ld = serde_json::json!({
+                "@context": "https://schema.org",
+                "@type": "Blog",
+                "name": config.site.title,
+                "description": config.site.description,
+                "url": url,
+                "blogPost": entries.iter().filter_map(|e| e.post.json_ld.clone()).map(|mut data| {
+                    // The context is inherited from the enclosing `Blog`
+                    if let Some(fields) = data.as_object_mut() {
+                        fields.remove("@context");
+                    }
+                    data
+                }).collect::<Vec<_>>(),
+            });
+            let html = template
+                .render(context! {
+                    site => &config.site,
+                    app => &app,
+                    collection => collection,
+                    path => &path,
+                    url => &url,
+                    posts => &entries,
+                    json_ld => Value::from_safe_string(script_json(&json_ld)?),
+                })
+                .with_context(|| format!("Failed to render {path} with {name}"))?;
+            write_page(&settings.output_dir.join(collection), &output::minify_html(config, html))?;
+            written += 1;
+        }
+    }
+
+    Ok(written)
+}
+
+fn write_page(dir: &Path, html: &str) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create page directory: {}", dir.display()))?;
+    let path = dir.join("index.html");
+    fs::write(&path, html).with_context(|| format!("Failed to write page: {}", path.display()))
+}
+
+/// `<meta>` and `<link>` tags for the document head, built from the post's SEO block
+fn meta_tags(seo: &SeoMetadata) -> String {
+    let mut tags = Vec::new();
+    let mut meta = |attribute: &str, key: &str, value: &str| {
+        tags.push(format!("<meta {attribute}=\"{key}\" content=\"{}\" />", attr(value)));
+    };
+
+    if let Some(description) = &seo.description {
+        meta("name", "description", description);
+    }
+    if seo.noindex {
+        meta("name", "robots", "noindex");
+    }
+
+    let og = &seo.open_graph;
+    meta("property", "og:type", &og.og_type);
+    meta("property", "og:title", &og.title);
+    if let Some(description) = &og.description {
+        meta("property", "og:description", description);
+    }
+    if let Some(url) = &og.url {
+        meta("property", "og:url", url);
+    }
+    if let Some(image) = &og.image {
+        meta("property", "og:image", image);
+    }
+    meta("property", "og:site_name", &og.site_name);
+    meta("property", "og:locale", &og.locale);
+
+    let article = &seo.article;
+    meta("property", "article:published_time", &article.published_time.to_rfc3339());
+    if let Some(modified) = article.modified_time {
+        meta("property", "article:modified_time", &modified.to_rfc3339());
+    }
+    if let Some(author) = &article.author {
+        meta("property", "article:author", author);
+    }
+    for tag in &article.tags {
+        meta("property", "article:tag", tag);
+    }
+
+    let twitter = &seo.twitter;
+    meta("name", "twitter:card", &twitter.card);
+    meta("name", "twitter:title", &twitter.title);
+    if let Some(description) = &twitter.description {
+        meta("name", "twitter:description", description);
+    }
+    if let Some(image) = &twitter.image {
+        meta("name", "twitter:image", image);
+    }
+
+    if let Some(canonical) = &seo.canonical_url {
+        tags.push(format!("<link rel=\"canonical\" href=\"{}\" />", attr(canonical)));
+    }
+    tags.join("\n    ")
+}
+
+/// JSON that is safe to place inside a `<script>` element
+fn script_json(value: &impl Serialize) -> Result<String> {
+    Ok(serde_json::to_string(value)?.replace("</", "<\\/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_json_cannot_close_the_script_element() {
+        let json = script_json(&serde_json::json!({ "content": "<p>x</p></script><script>alert(1)</script>" })).unwrap();
+        assert!(!json.contains("</"));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()["content"],
+            "<p>x</p></script><script>alert(1)</script>"
+        );
+    }
+
+    #[test]
+    fn test_pages_load_the_built_app() {
+        let dir = std::env::temp_dir().join(format!("site-builder-prerender-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("manifest.json");
+        fs::write(
+            &manifest,
+            r#"{
+                "index.html": { "file": "assets/index-B1x.js", "src": "index.html", "isEntry": true,
+                                "css": ["assets/index-C2y.css"], "imports": ["_vendor-D3z.js"] },
+                "_vendor-D3z.js": { "file": "assets/vendor-D3z.js", "css": ["assets/vendor-E4w.css"],
+                                    "imports": ["index.html"] }
+            }"#,
+        )
+        .unwrap();
+        let mut settings = PrerenderSettings {
+            vite_manifest: manifest,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            app_assets(&settings).unwrap(),
+            AppAssets {
+                script: "/assets/index-B1x.js".to_string(),
+                stylesheets: vec!["/assets/index-C2y.css".to_string(), "/assets/vendor-E4w.css".to_string()],
+                preloads: vec!["/assets/vendor-D3z.js".to_string()],
+            }
+        );
+
+        settings.vite_entry = "src/main.tsx".to_string();
+        assert!(app_assets(&settings).unwrap_err().to_string().contains("No `src/main.tsx` entry"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pages_are_written_into_the_built_app() {
+        let dir = std::env::temp_dir().join(format!("site-builder-prerender-dist-{}", std::process::id()));
+        let (dist, templates) = (dir.join("dist"), dir.join("templates"));
+        fs::create_dir_all(dist.join(".vite")).unwrap();
+        fs::create_dir_all(&templates).unwrap();
+        fs::write(dist.join(".vite/manifest.json"), r#"{ "index.html": { "file": "assets/index-B1x.js" } }"#).unwrap();
+        fs::write(templates.join("index.html"), r#"<script src="{{ app.script }}"></script>"#).unwrap();
+        let config = Config {
+            public_dir: dir.join("public"),
+            prerender: PrerenderSettings {
+                template_dir: templates,
+                index_template: Some("index.html".to_string()),
+                output_dir: dist.clone(),
+                vite_manifest: dist.join(".vite/manifest.json"),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let builds = [CollectionBuild {
+            collection: config.collections().remove(0),
+            posts: Vec::new(),
+            processed: 0,
+            skipped: 0,
+            errors: Vec::new(),
+        }];
+
+        assert_eq!(write_pages(&config, &builds).unwrap(), 1);
+        assert!(fs::read_to_string(dist.join("blog/index.html")).unwrap().contains("index-B1x.js"));
+        assert!(!config.public_dir.exists(), "Vite copies public_dir into the build, so pages skip it");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::assets::AssetManifestFile;
+use crate::config::Config;
+use crate::pagination::{ArchiveChunk, IndexManifest, IndexPage};
+use crate::search::SearchIndex;
+use crate::sections::SectionIndex;
+use crate::{Post, PostIndex};
+
+/// Stamped into every generated file as `schema_version`. Bump it on any breaking
+/// change to the output structs; cached posts with another version are rebuilt.
+pub const SCHEMA_VERSION: u32 = 11;
+
+/// SHA-256 of the published schemas of each version, checked by the tests. A changed
+/// schema needs a new `SCHEMA_VERSION` and a new line here; existing lines never change.
+#[cfg(test)]
+const PUBLISHED_SCHEMAS: &[(u32, &str)] = &[
+    (11, "c9e3423703a15200d31a22d9224b408f7ae2939b4789dfcf5ce4c924d2097228"),
+];
+
+/// Published schema file names and their root types
+fn schemas() -> Vec<(&'static str, Schema)> {
+    vec![
+        ("post", schema_for::<Post>()),
+        ("post-index", schema_for::<PostIndex>()),
+        ("section-index", schema_for::<SectionIndex>()),
+        ("index-manifest", schema_for::<IndexManifest>()),
+        ("index-page", schema_for::<IndexPage>()),
+        ("archive", schema_for::<ArchiveChunk>()),
+        ("search-index", schema_for::<SearchIndex>()),
+        ("asset-manifest", schema_for::<AssetManifestFile>()),
+    ]
+}
+
+fn schema_for<T: JsonSchema>() -> Schema {
+    SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<T>()
+}
+
+/// Write `<public_dir>/schemas/v<SCHEMA_VERSION>/<name>.schema.json` for every output type.
+/// Returns the number of schemas written.
+pub fn write_schemas(config: &Config) -> Result<usize> {
+    let relative_dir = format!("schemas/v{SCHEMA_VERSION}");
+    let dir = config.public_dir.join(&relative_dir);
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create schema directory: {}", dir.display()))?;
+
+    let schemas = schemas();
+    for (name, mut schema) in schemas.iter().cloned() {
+        let file_name = format!("{name}.schema.json");
+        if let Some(id) = config.absolute_url(&format!("{relative_dir}/{file_name}")) {
+            schema.insert("$id".to_string(), id.into());
+        }
+        let path = dir.join(file_name);
+        fs::write(&path, serde_json::to_string_pretty(&schema)?)
+            .with_context(|| format!("Failed to write JSON Schema: {}", path.display()))?;
+    }
+
+    Ok(schemas.len())
+}
+
+/// Check a value against the schema of its type before it is written to `path`.
+/// Only debug builds validate, which includes `cargo test`. Release builds, like those that
+/// publish with `--production`, never check their output, so mismatches surface only in
+/// the tests and development builds.
+pub fn validate<T: JsonSchema + Serialize>(value: &T, path: &Path) -> Result<()> {
+    if !cfg!(debug_assertions) {
+        return Ok(());
+    }
+
+    static VALIDATORS: OnceLock<Mutex<HashMap<String, Arc<jsonschema::Validator>>>> = OnceLock::new();
+    let validator = {
+        let mut validators = VALIDATORS.get_or_init(Default::default).lock().unwrap();
+        match validators.get(T::schema_name().as_ref()) {
+            Some(validator) => validator.clone(),
+            None => {
+                let schema = serde_json::to_value(schema_for::<T>())?;
+                let validator = Arc::new(
+                    jsonschema::validator_for(&schema)
+                        .map_err(|e| anyhow::anyhow!("Invalid schema for {}: {e}", T::schema_name()))?,
+                );
+                validators.insert(T::schema_name().into_owned(), validator.clone());
+                validator
+            }
+        }
+    };
+
+    let instance = serde_json::to_value(value)?;
+    let errors: Vec<String> = validator
+        .iter_errors(&instance)
+        .map(|error| format!("{} at `{}`", error, error.instance_path))
+        .collect();
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Generated {} does not match its {} schema:\n  {}",
+            path.display(),
+            T::schema_name(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas_compile_and_require_the_version() {
+        for (name, schema) in schemas() {
+            let schema = serde_json::to_value(schema).unwrap();
+            assert!(jsonschema::validator_for(&schema).is_ok(), "{name} is not a valid schema");
+        }
+
+        let post_schema = serde_json::to_value(schema_for::<Post>()).unwrap();
+        let validator = jsonschema::validator_for(&post_schema).unwrap();
+        let mut post = serde_json::json!({
+            "title": "Hello",
+            "date": "2024-03-20T12:00:00Z",
+            "slug": "hello",
+            "content": "<p>Hi</p>",
+            "tags": ["intro"],
+        });
+        assert!(!validator.is_valid(&post));
+        post["schema_version"] = SCHEMA_VERSION.into();
+        assert!(validator.is_valid(&post));
+    }
+
+    #[test]
+    fn test_schema_changes_bump_the_version() {
+        use sha2::{Digest, Sha256};
+
+        let published = serde_json::to_string(&schemas()).unwrap();
+        let hash = format!("{:x}", Sha256::digest(published));
+        let (latest, pinned) = PUBLISHED_SCHEMAS.last().unwrap();
+        assert_eq!(
+            (*latest, pinned.to_string()),
+            (SCHEMA_VERSION, hash),
+            "The output schemas changed. Bump SCHEMA_VERSION and add its hash to PUBLISHED_SCHEMAS."
+        );
+        for (i, (version, hash)) in PUBLISHED_SCHEMAS.iter().enumerate() {
+            assert!(
+                PUBLISHED_SCHEMAS[..i].iter().all(|(v, h)| v < version && h != hash),
+                "PUBLISHED_SCHEMAS must only grow, with one new version per schema change"
+            );
+        }
+    }
+}
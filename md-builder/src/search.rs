@@ -0,0 +1,374 @@
+use anyhow::{Context, Result};
+use rust_stemmers::{Algorithm, Stemmer};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::sync::OnceLock;
+use ts_rs::TS;
+
+use crate::config::Config;
+use crate::schema::{self, SCHEMA_VERSION};
+use crate::{is_indexed, post_language, post_path, CollectionBuild, Post};
+
+/// Bumped whenever the layout of the index file changes
+const SEARCH_INDEX_VERSION: u32 = 2;
+
+/// Indexed fields, in the order used by the postings
+const FIELDS: [&str; 4] = ["title", "headings", "tags", "body"];
+
+/// Fields whose sentences are stored as excerpt snippets
+const EXCERPT_FIELDS: [usize; 2] = [1, 3];
+
+/// Longest snippet, in characters; longer sentences are cut at a word boundary.
+/// Mentioned in the `SearchDoc::snippets` docs.
+const SNIPPET_LENGTH: usize = 160;
+
+/// Snippets stored per post. Matches further down only count for ranking.
+const MAX_SNIPPETS: usize = 12;
+
+const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "because",
+    "been", "before", "being", "between", "both", "but", "by", "can", "could", "did", "do", "does",
+    "doing", "during", "each", "few", "for", "from", "further", "had", "has", "have", "having",
+    "he", "her", "here", "hers", "him", "his", "how", "i", "if", "in", "into", "is", "it", "its",
+    "itself", "just", "me", "more", "most", "my", "no", "nor", "not", "now", "of", "off", "on",
+    "once", "only", "or", "other", "our", "ours", "out", "over", "own", "same", "she", "should",
+    "so", "some", "such", "than", "that", "the", "their", "theirs", "them", "then", "there",
+    "these", "they", "this", "those", "through", "to", "too", "under", "until", "up", "very",
+    "was", "we", "were", "what", "when", "where", "which", "while", "who", "whom", "why", "will",
+    "with", "would", "you", "your", "yours",
+];
+
+const NORWEGIAN_STOP_WORDS: &[&str] = &[
+    "alle", "at", "av", "bare", "begge", "ble", "blei", "bli", "blir", "blitt", "både", "da",
+    "de", "deg", "dei", "deim", "deira", "deires", "dem", "den", "denne", "der", "dere", "deres",
+    "det", "dette", "di", "din", "disse", "ditt", "du", "dykk", "dykkar", "eg", "ein", "eit",
+    "eitt", "eller", "elles", "en", "enn", "er", "et", "ett", "etter", "for", "fordi", "fra",
+    "før", "ha", "hadde", "han", "hans", "har", "hennar", "henne", "hennes", "her", "hjå", "ho",
+    "hoe", "honom", "hoss", "hossen", "hun", "hva", "hvem", "hver", "hvilke", "hvilken", "hvis",
+    "hvor", "hvordan", "hvorfor", "i", "ikke", "ikkje", "ingen", "ingi", "inkje", "inn", "inni",
+    "ja", "jeg", "kan", "kom", "korleis", "korso", "kun", "kunne", "kva", "kvar", "kvarhelst",
+    "kven", "kvi", "kvifor", "man", "mange", "me", "med", "medan", "meg", "meget", "mellom",
+    "men", "mi", "min", "mine", "mitt", "mot", "mykje", "ned", "no", "noe", "noen", "noka",
+    "noko", "nokon", "nokor", "nokre", "nå", "når", "og", "også", "om", "opp", "oss", "over",
+    "på", "samme", "seg", "selv", "si", "sia", "sidan", "siden", "sin", "sine", "sitt", "sjøl",
+    "skal", "skulle", "slik", "so", "som", "somme", "somt", "så", "sånn", "til", "um", "upp",
+    "ut", "uten", "var", "vart", "varte", "ved", "vere", "verte", "vi", "vil", "ville", "vore",
+    "vors", "vort", "vår", "være", "vært", "å",
+];
+
+/// A post in the index with the short texts needed to show a result
+#[derive(Serialize, TS, JsonSchema)]
+pub struct SearchDoc {
+    url: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    description: Option<String>,
+    tags: Vec<String>,
+    /// The first distinct sentences of the headings and body, cut to 160 characters,
+    /// to show as highlighted excerpts
+    snippets: Vec<String>,
+}
+
+/// Inverted index for one language, loaded lazily by the frontend
+#[derive(Serialize, TS, JsonSchema)]
+pub struct SearchIndex {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    schema_version: u32,
+    version: u32,
+    language: String,
+    fields: [&'static str; 4],
+    docs: Vec<SearchDoc>,
+    /// Stemmed term to postings. Each posting is `[doc, field, count, snippet, snippet, ...]`:
+    /// how often the term occurs in the field and which of the doc's snippets contain it.
+    terms: BTreeMap<String, Vec<Vec<u32>>>,
+}
+
+/// Tokenizer with the stemmer and stop words of one language
+pub struct Analyzer {
+    stemmer: Option<Stemmer>,
+    stop_words: &'static [&'static str],
+}
+
+impl Analyzer {
+    /// English and Norwegian get stemming and stop words, other languages are only lowercased
+    pub fn for_language(language: &str) -> Self {
+        match language.split(['-', '_']).next().unwrap_or_default() {
+            "en" => Analyzer {
+                stemmer: Some(Stemmer::create(Algorithm::English)),
+                stop_words: ENGLISH_STOP_WORDS,
+            },
+            "no" | "nb" | "nn" => Analyzer {
+                stemmer: Some(Stemmer::create(Algorithm::Norwegian)),
+                stop_words: NORWEGIAN_STOP_WORDS,
+            },
+            _ => Analyzer {
+                stemmer: None,
+                stop_words: &[],
+            },
+        }
+    }
+
+    /// Split text into stemmed terms with the UTF-16 offset of each word
+    pub fn tokenize(&self, text: &str) -> Vec<(String, u32)> {
+        let mut tokens = Vec::new();
+        let mut offset = 0u32;
+        let mut word_start = None;
+        let mut word = String::new();
+
+        for c in text.chars().chain(std::iter::once(' ')) {
+            if c.is_alphanumeric() {
+                word_start.get_or_insert(offset);
+                word.extend(c.to_lowercase());
+            } else if let Some(start) = word_start.take() {
+                if word.chars().count() > 1 && !self.stop_words.contains(&word.as_str()) {
+                    let term = match &self.stemmer {
+                        Some(stemmer) => stemmer.stem(&word).into_owned(),
+                        None => word.clone(),
+                    };
+                    tokens.push((term, start));
+                }
+                word.clear();
+            }
+            offset += c.len_utf16() as u32;
+        }
+
+        tokens
+    }
+}
+
+/// Write one search index per language to `<public_dir>/<search.output_dir>/<language>.json`.
+/// Returns the number of indexes written.
+pub fn write_search_indexes(config: &Config, builds: &[CollectionBuild]) -> Result<usize> {
+    if !config.search.enabled {
+        return Ok(0);
+    }
+
+    let mut by_language: BTreeMap<String, Vec<(&str, &Post)>> = BTreeMap::new();
+    for build in builds
+        .iter()
+        .filter(|b| config.search.collections.contains(&b.collection.name))
+    {
+        for post in build.posts.iter().filter(|post| is_indexed(post)) {
+            by_language
+                .entry(post_language(config, post).to_string())
+                .or_default()
+                .push((build.collection.name.as_str(), post));
+        }
+    }
+
+    let output_dir = config.public_dir.join(&config.search.output_dir);
+    fs::create_dir_all(&output_dir)
+        .with_context(|| format!("Failed to create search index directory: {}", output_dir.display()))?;
+
+    for (language, posts) in &by_language {
+        let index = build_index(language, posts);
+        let path = output_dir.join(format!("{language}.json"));
+        schema::validate(&index, &path)?;
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create search index: {}", path.display()))?;
+        serde_json::to_writer(file, &index)
+            .with_context(|| format!("Failed to write search index to: {}", path.display()))?;
+    }
+
+    Ok(by_language.len())
+}
+
+fn build_index(language: &str, posts: &[(&str, &Post)]) -> SearchIndex {
+    let analyzer = Analyzer::for_language(language);
+    let mut docs = Vec::with_capacity(posts.len());
+    let mut terms: BTreeMap<String, Vec<Vec<u32>>> = BTreeMap::new();
+
+    for (doc_id, (collection, post)) in posts.iter().enumerate() {
+        let (headings, body) = extract_text(&post.content);
+        let fields = [post.title.clone(), headings, post.tags.join(", "), body];
+        let mut snippets: Vec<String> = Vec::new();
+
+        for (field_id, text) in fields.iter().enumerate() {
+            // Occurrences and containing snippets of each term
+            let mut postings: BTreeMap<String, (u32, Vec<u32>)> = BTreeMap::new();
+            let excerpted = EXCERPT_FIELDS.contains(&field_id);
+            let sentences = if excerpted { split_sentences(text) } else { vec![text.as_str()] };
+
+            for sentence in sentences {
+                // Headings reappear in the body and share their snippet
+                let snippet = excerpted.then(|| shorten(sentence)).and_then(|text| {
+                    match snippets.iter().position(|s| *s == text) {
+                        Some(id) => Some(id as u32),
+                        None if snippets.len() < MAX_SNIPPETS => {
+                            snippets.push(text);
+                            Some(snippets.len() as u32 - 1)
+                        }
+                        None => None,
+                    }
+                });
+                // Words cut off the end of a shortened snippet are not in it
+                let stored = snippet.map_or(0, |id| snippets[id as usize].encode_utf16().count() as u32);
+                for (term, offset) in analyzer.tokenize(sentence) {
+                    let (count, ids) = postings.entry(term).or_default();
+                    *count += 1;
+                    if let Some(id) = snippet.filter(|_| offset < stored) {
+                        if ids.last() != Some(&id) {
+                            ids.push(id);
+                        }
+                    }
+                }
+            }
+
+            for (term, (count, ids)) in postings {
+                let mut posting = vec![doc_id as u32, field_id as u32, count];
+                posting.extend(ids);
+                terms.entry(term).or_default().push(posting);
+            }
+        }
+
+        docs.push(SearchDoc {
+            url: post_path(collection, &post.slug),
+            title: post.title.clone(),
+            description: post.description.clone(),
+            tags: post.tags.clone(),
+            snippets,
+        });
+    }
+
+    SearchIndex {
+        schema_version: SCHEMA_VERSION,
+        version: SEARCH_INDEX_VERSION,
+        language: language.to_string(),
+        fields: FIELDS,
+        docs,
+        terms,
+    }
+}
+
+/// Sentences of plain text, ending at `.`, `!` or `?` before whitespace or at a line break
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '\n' => Some(i),
+            '.' | '!' | '?' if chars.peek().is_none_or(|(_, next)| next.is_whitespace()) => Some(i + 1),
+            _ => None,
+        };
+        if let Some(end) = end {
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+/// A sentence cut to `SNIPPET_LENGTH` characters at a word boundary, marked with `…`
+fn shorten(sentence: &str) -> String {
+    match sentence.char_indices().nth(SNIPPET_LENGTH) {
+        None => sentence.to_string(),
+        Some((limit, _)) => {
+            let cut = sentence[..limit].rfind(char::is_whitespace).unwrap_or(limit);
+            format!("{}…", sentence[..cut].trim_end())
+        }
+    }
+}
+
+/// Plain text of the headings and of the whole body, with code blocks and inline code removed.
+/// Headings, paragraphs and other blocks end up on lines of their own.
+fn extract_text(html: &str) -> (String, String) {
+    static CODE: OnceLock<regex_lite::Regex> = OnceLock::new();
+    static HEADING: OnceLock<regex_lite::Regex> = OnceLock::new();
+    static BLOCK_END: OnceLock<regex_lite::Regex> = OnceLock::new();
+    static TAG: OnceLock<regex_lite::Regex> = OnceLock::new();
+
+    let code = CODE.get_or_init(|| regex_lite::Regex::new(r"(?s)<pre[\s>].*?</pre>|<code>.*?</code>").unwrap());
+    let heading = HEADING.get_or_init(|| regex_lite::Regex::new(r"(?s)<h[1-6][^>]*>(.*?)</h[1-6]>").unwrap());
+    let block_end = BLOCK_END.get_or_init(|| {
+        regex_lite::Regex::new(r"</(?:p|h[1-6]|li|blockquote|dt|dd|th|td|figcaption)>").unwrap()
+    });
+    let tag = TAG.get_or_init(|| regex_lite::Regex::new(r"<[^>]+>").unwrap());
+
+    let without_code = code.replace_all(html, " ");
+    let strip = |fragment: &str| {
+        let text = block_end.replace_all(fragment, "\n");
+        let text = tag.replace_all(&text, " ");
+        let text = html_escape::decode_html_entities(&text);
+        text.lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let headings = heading
+        .captures_iter(&without_code)
+        .map(|caps| strip(&caps[1]))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (headings, strip(&without_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_specific_tokenizing() {
+        let english = Analyzer::for_language("en-US");
+        assert_eq!(
+            english.tokenize("The farms are monitoring"),
+            vec![("farm".to_string(), 4), ("monitor".to_string(), 14)]
+        );
+
+        let norwegian = Analyzer::for_language("nb");
+        let terms: Vec<String> = norwegian
+            .tokenize("Fiskene i merdene og oppdrettsanleggene")
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect();
+        assert_eq!(terms, vec!["fisk", "merd", "oppdrettsanlegg"]);
+    }
+
+    #[test]
+    fn test_code_is_excluded() {
+        let (headings, body) = extract_text(
+            "<h2>Setup &amp; run</h2><p>Call <code>foo()</code> now</p><pre class=\"highlight\"><code>let x = 1;</code></pre>",
+        );
+        assert_eq!(headings, "Setup & run");
+        assert_eq!(body, "Setup & run\nCall now");
+    }
+
+    #[test]
+    fn test_docs_store_short_snippets() {
+        let long_sentence = "Salmon ".repeat(60);
+        let post = Post {
+            slug: "farming".to_string(),
+            title: "Fish farming".to_string(),
+            tags: vec!["salmon".to_string()],
+            content: format!(
+                "<h2>Feeding</h2><p>Salmon eat pellets. {}. {}</p>",
+                long_sentence.trim(),
+                (1..=20).map(|n| format!("Sentence {n}. ")).collect::<String>()
+            ),
+            ..Post::default()
+        };
+        let index = build_index("en", &[("blog", &post)]);
+        let doc = &index.docs[0];
+
+        assert_eq!(doc.url, "/blog/farming");
+        assert_eq!(doc.snippets.len(), MAX_SNIPPETS);
+        assert_eq!(&doc.snippets[..2], ["Feeding", "Salmon eat pellets."]);
+        assert!(doc.snippets[2].ends_with('…'));
+        assert!(doc.snippets.iter().all(|s| s.chars().count() <= SNIPPET_LENGTH + 1));
+
+        // Tag once, and body matches in the second and the shortened third snippet
+        let postings = &index.terms["salmon"];
+        assert_eq!(postings[0], vec![0, 2, 1]);
+        assert_eq!(postings[1], vec![0, 3, 61, 1, 2]);
+        assert_eq!(index.terms["feed"], vec![vec![0, 1, 1, 0], vec![0, 3, 1, 0]]);
+        assert_eq!(index.terms["fish"], vec![vec![0, 0, 1]]);
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Component, Path};
+use syntect::parsing::SyntaxSet;
+use ts_rs::TS;
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::output;
+use crate::schema::{self, SCHEMA_VERSION};
+use crate::{parse_metadata_and_content, PostSummary};
+
+/// File name that holds the title and description of a section
+pub const SECTION_INDEX_FILE: &str = "_index.md";
+
+/// Title, description and intro text of a section, read from its `_index.md`
+#[derive(Default)]
+pub struct SectionMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(Clone, Serialize, TS, JsonSchema)]
+pub struct SectionSummary {
+    pub path: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub description: Option<String>,
+    pub post_count: usize,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+pub struct SectionIndex {
+    /// Output format version, see `schema::SCHEMA_VERSION`
+    pub schema_version: u32,
+    pub path: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subsections: Vec<SectionSummary>,
+    pub posts: Vec<PostSummary>,
+}
+
+/// Turn a path relative to the content directory into a `/`-separated section path
+/// or slug, independent of the platform path separator
+pub fn path_to_slug(relative: &Path) -> String {
+    relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Parent section of a section path, `None` for the root section
+pub fn parent_section(section: &str) -> Option<&str> {
+    if section.is_empty() {
+        None
+    } else {
+        Some(section.rsplit_once('/').map(|(parent, _)| parent).unwrap_or(""))
+    }
+}
+
+/// Whether `section` is `ancestor` itself or nested somewhere below it
+pub fn is_within(section: &str, ancestor: &str) -> bool {
+    ancestor.is_empty()
+        || section == ancestor
+        || section
+            .strip_prefix(ancestor)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Read every `_index.md` below the content directory, keyed by section path
+pub fn load_section_metadata(
+    content_dir: &Path,
+    syntax_set: &SyntaxSet,
+) -> Result<BTreeMap<String, SectionMetadata>> {
+    let mut sections = BTreeMap::new();
+
+    for entry in WalkDir::new(content_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == SECTION_INDEX_FILE)
+    {
+        let path = entry.path();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read section file: {}", path.display()))?;
+        let (metadata, html) = parse_metadata_and_content(&content, syntax_set, |_| None)
+            .with_context(|| format!("Failed to parse section file: {}", path.display()))?;
+
+        let relative_dir = path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(content_dir).ok())
+            .unwrap_or(Path::new(""));
+
+        sections.insert(
+            path_to_slug(relative_dir),
+            SectionMetadata {
+                title: metadata.title,
+                description: metadata.description,
+                content: Some(html).filter(|html| !html.trim().is_empty()),
+            },
+        );
+    }
+
+    Ok(sections)
+}
+
+/// Build one index per section. A section lists the posts of all its subsections,
+/// and every ancestor of a section with posts gets an index of its own.
+pub fn build_section_indexes(
+    posts: &[PostSummary],
+    mut metadata: BTreeMap<String, SectionMetadata>,
+) -> Vec<SectionIndex> {
+    let mut paths: Vec<String> = metadata.keys().cloned().collect();
+    for post in posts {
+        let mut section = Some(post.section.as_str());
+        while let Some(path) = section {
+            paths.push(path.to_string());
+            section = parent_section(path);
+        }
+    }
+    paths.sort();
+    paths.dedup();
+
+    let summaries: BTreeMap<&str, SectionSummary> = paths
+        .iter()
+        .map(|path| {
+            let meta = metadata.get(path);
+            let summary = SectionSummary {
+                path: path.clone(),
+                title: section_title(path, meta),
+                description: meta.and_then(|m| m.description.clone()),
+                post_count: posts.iter().filter(|p| is_within(&p.section, path)).count(),
+            };
+            (path.as_str(), summary)
+        })
+        .collect();
+
+    paths
+        .iter()
+        .map(|path| {
+            let meta = metadata.remove(path).unwrap_or_default();
+            let subsections = summaries
+                .values()
+                .filter(|s| parent_section(&s.path) == Some(path.as_str()))
+                .cloned()
+                .collect();
+
+            SectionIndex {
+                schema_version: SCHEMA_VERSION,
+                path: path.clone(),
+                title: section_title(path, Some(&meta)),
+                description: meta.description,
+                content: meta.content,
+                subsections,
+                posts: posts
+                    .iter()
+                    .filter(|p| is_within(&p.section, path))
+                    .cloned()
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Write `index.json` for every nested section. The root section is written
+/// separately as the main post index.
+pub fn write_section_indexes(config: &Config, output_dir: &Path, sections: &[SectionIndex]) -> Result<()> {
+    for section in sections.iter().filter(|s| !s.path.is_empty()) {
+        let index_path = output_dir.join(&section.path).join("index.json");
+        if let Some(parent) = index_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create section directory: {}", parent.display()))?;
+        }
+        schema::validate(section, &index_path)?;
+        let index_file = File::create(&index_path)
+            .with_context(|| format!("Failed to create section index: {}", index_path.display()))?;
+        output::to_writer(config, index_file, section)
+            .with_context(|| format!("Failed to write section index to: {}", index_path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn section_title(path: &str, metadata: Option<&SectionMetadata>) -> String {
+    metadata
+        .and_then(|m| m.title.clone())
+        .unwrap_or_else(|| path.rsplit('/').next().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_hierarchy() {
+        assert_eq!(path_to_slug(Path::new("blog/2024/intro")), "blog/2024/intro");
+        assert_eq!(parent_section("blog/2024"), Some("blog"));
+        assert_eq!(parent_section("blog"), Some(""));
+        assert_eq!(parent_section(""), None);
+
+        assert!(is_within("blog/2024", "blog"));
+        assert!(is_within("blog", ""));
+        assert!(!is_within("blogroll", "blog"));
+    }
+}
@@ -0,0 +1,231 @@
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::config::Config;
+
+/// Recommended maximum lengths before search engines and social cards truncate
+const MAX_TITLE_LENGTH: usize = 60;
+const MIN_DESCRIPTION_LENGTH: usize = 50;
+const MAX_DESCRIPTION_LENGTH: usize = 160;
+const MAX_OG_TITLE_LENGTH: usize = 95;
+const MAX_OG_DESCRIPTION_LENGTH: usize = 200;
+
+/// Per-post overrides from the `seo` frontmatter block
+#[derive(Default, Deserialize)]
+pub struct SeoOverrides {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub canonical: Option<String>,
+    pub twitter_card: Option<String>,
+    #[serde(default)]
+    pub noindex: bool,
+}
+
+/// Everything the frontend needs for `<title>`, meta, Open Graph and Twitter tags
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct SeoMetadata {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub canonical_url: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub noindex: bool,
+    pub open_graph: OpenGraph,
+    pub twitter: TwitterCard,
+    pub article: ArticleMetadata,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct OpenGraph {
+    #[serde(rename = "type")]
+    pub og_type: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub image: Option<String>,
+    pub site_name: String,
+    pub locale: String,
+}
+
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct TwitterCard {
+    pub card: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub image: Option<String>,
+}
+
+/// `article:*` Open Graph properties
+#[derive(Serialize, Deserialize, TS, JsonSchema)]
+pub struct ArticleMetadata {
+    pub published_time: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub modified_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Post data the SEO block is derived from
+pub struct SeoInput<'a> {
+    pub path: &'a str,
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    /// Cover or generated preview card, used unless the frontmatter names an image
+    pub image: Option<&'a str>,
+    pub date: DateTime<Utc>,
+    pub updated: Option<DateTime<Utc>>,
+    pub language: Option<&'a str>,
+    pub tags: &'a [String],
+}
+
+/// Compute the SEO block for a post. URLs are absolute when `site.url` is set.
+pub fn build_seo(config: &Config, input: &SeoInput, overrides: SeoOverrides) -> SeoMetadata {
+    let absolute = |path: &str| config.absolute_url(path).unwrap_or_else(|| path.to_string());
+
+    let title = overrides.title.unwrap_or_else(|| input.title.to_string());
+    let description = overrides
+        .description
+        .or_else(|| input.description.map(str::to_string));
+    let image = overrides
+        .image
+        .as_deref()
+        .or(input.image)
+        .map(absolute);
+    let canonical_url = overrides
+        .canonical
+        .or_else(|| config.absolute_url(input.path));
+    let card = overrides.twitter_card.unwrap_or_else(|| {
+        if image.is_some() { "summary_large_image" } else { "summary" }.to_string()
+    });
+    let language = input.language.unwrap_or(&config.site.language);
+
+    SeoMetadata {
+        title: title.clone(),
+        description: description.clone(),
+        canonical_url: canonical_url.clone(),
+        noindex: overrides.noindex,
+        open_graph: OpenGraph {
+            og_type: "article".to_string(),
+            title: title.clone(),
+            description: description.clone(),
+            url: canonical_url,
+            image: image.clone(),
+            site_name: config.site.title.clone(),
+            locale: og_locale(language),
+        },
+        twitter: TwitterCard {
+            card,
+            title,
+            description,
+            image,
+        },
+        article: ArticleMetadata {
+            published_time: input.date,
+            modified_time: input.updated,
+            author: config.site.author.clone(),
+            tags: input.tags.to_vec(),
+        },
+    }
+}
+
+/// Warnings for values that search engines or social cards will truncate or reject
+pub fn validate(seo: &SeoMetadata) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let title_length = seo.title.chars().count();
+    if title_length > MAX_TITLE_LENGTH {
+        warnings.push(format!(
+            "title is {title_length} characters, search results show about {MAX_TITLE_LENGTH}"
+        ));
+    }
+    if title_length > MAX_OG_TITLE_LENGTH {
+        warnings.push(format!("Open Graph title exceeds {MAX_OG_TITLE_LENGTH} characters"));
+    }
+
+    match seo.description.as_deref().map(|d| d.chars().count()) {
+        None => warnings.push("missing description".to_string()),
+        Some(length) if length < MIN_DESCRIPTION_LENGTH => warnings.push(format!(
+            "description is {length} characters, aim for at least {MIN_DESCRIPTION_LENGTH}"
+        )),
+        Some(length) if length > MAX_DESCRIPTION_LENGTH => {
+            warnings.push(format!(
+                "description is {length} characters, search results show about {MAX_DESCRIPTION_LENGTH}"
+            ));
+            if length > MAX_OG_DESCRIPTION_LENGTH {
+                warnings.push(format!(
+                    "Open Graph description exceeds {MAX_OG_DESCRIPTION_LENGTH} characters"
+                ));
+            }
+        }
+        Some(_) => {}
+    }
+
+    warnings
+}
+
+/// Open Graph locale (`en_US`, `nb_NO`) for a language code
+fn og_locale(language: &str) -> String {
+    match language {
+        "en" => "en_US".to_string(),
+        "no" | "nb" => "nb_NO".to_string(),
+        "nn" => "nn_NO".to_string(),
+        other => other.replace('-', "_"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_and_length_warnings() {
+        let mut config = Config::default();
+        config.site.url = Some("https://example.com".to_string());
+        let tags = vec!["AI".to_string()];
+        let input = SeoInput {
+            path: "/blog/intro",
+            title: "A title that is far too long to fit in a search result snippet",
+            description: Some("Too short"),
+            image: Some("/images/blog/intro-cover.png"),
+            date: Utc::now(),
+            updated: None,
+            language: Some("no"),
+            tags: &tags,
+        };
+
+        let seo = build_seo(&config, &input, SeoOverrides::default());
+        assert_eq!(seo.canonical_url.as_deref(), Some("https://example.com/blog/intro"));
+        assert_eq!(seo.open_graph.image.as_deref(), Some("https://example.com/images/blog/intro-cover.png"));
+        assert_eq!(seo.open_graph.locale, "nb_NO");
+        assert_eq!(seo.twitter.card, "summary_large_image");
+        assert_eq!(validate(&seo).len(), 2);
+
+        let overrides = SeoOverrides {
+            title: Some("Short title".to_string()),
+            description: Some("A description long enough to satisfy the lower limit.".to_string()),
+            ..Default::default()
+        };
+        let seo = build_seo(&config, &SeoInput { image: None, ..input }, overrides);
+        assert_eq!(seo.twitter.card, "summary");
+        assert!(validate(&seo).is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{Context, Result};
+use html_escape::{encode_double_quoted_attribute as attr, encode_text as text};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::config::Config;
+use crate::{is_indexed, post_path, CollectionBuild, Post};
+
+struct SitemapUrl<'a> {
+    loc: String,
+    post: Option<&'a Post>,
+}
+
+/// Write `sitemap.xml` with the static routes and every post, including hreflang
+/// alternates for posts that share a `translation_key`. Returns whether it was written.
+pub fn write_sitemap(config: &Config, builds: &[CollectionBuild]) -> Result<bool> {
+    if !config.sitemap.enabled {
+        return Ok(false);
+    }
+    if config.site.url.is_none() {
+        eprintln!("⚠️  Skipping sitemap.xml: `site.url` is not set");
+        return Ok(false);
+    }
+
+    let mut urls: Vec<SitemapUrl> = config
+        .sitemap
+        .static_routes
+        .iter()
+        .filter_map(|route| config.absolute_url(route))
+        .map(|loc| SitemapUrl { loc, post: None })
+        .collect();
+
+    // Language versions of each translated post, keyed by translation key
+    let mut translations: BTreeMap<&str, Vec<(String, String)>> = BTreeMap::new();
+
+    for build in builds.iter().filter(|b| {
+        config.sitemap.collections.is_empty() || config.sitemap.collections.contains(&b.collection.name)
+    }) {
+        for post in build.posts.iter().filter(|post| is_indexed(post)) {
+            let Some(loc) = config.absolute_url(&post_path(&build.collection.name, &post.slug)) else {
+                continue;
+            };
+            if let Some(key) = &post.translation_key {
+                let language = post.language.clone().unwrap_or_else(|| config.site.language.clone());
+                translations.entry(key).or_default().push((language, loc.clone()));
+            }
+            urls.push(SitemapUrl { loc, post: Some(post) });
+        }
+    }
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n");
+
+    for url in &urls {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", text(&url.loc)));
+        if let Some(post) = url.post {
+            let lastmod = post.updated.unwrap_or(post.date);
+            xml.push_str(&format!("    <lastmod>{}</lastmod>\n", lastmod.format("%Y-%m-%d")));
+
+            let alternates = post
+                .translation_key
+                .as_deref()
+                .and_then(|key| translations.get(key))
+                .filter(|versions| versions.len() > 1);
+            if let Some(versions) = alternates {
+                for (language, href) in versions {
+                    xml.push_str(&format!(
+                        "    <xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\" />\n",
+                        attr(language),
+                        attr(href)
+                    ));
+                }
+                if let Some((_, href)) = versions.iter().find(|(l, _)| *l == config.site.language) {
+                    xml.push_str(&format!(
+                        "    <xhtml:link rel=\"alternate\" hreflang=\"x-default\" href=\"{}\" />\n",
+                        attr(href)
+                    ));
+                }
+            }
+        }
+        xml.push_str("  </url>\n");
+    }
+
+    xml.push_str("</urlset>\n");
+
+    fs::create_dir_all(&config.public_dir)
+        .with_context(|| format!("Failed to create public directory: {}", config.public_dir.display()))?;
+    let path = config.public_dir.join("sitemap.xml");
+    fs::write(&path, xml).with_context(|| format!("Failed to write sitemap: {}", path.display()))?;
+
+    Ok(true)
+}
+
+/// Write `robots.txt` from the configured rules, pointing crawlers at the sitemap
+pub fn write_robots(config: &Config) -> Result<bool> {
+    if !config.robots.enabled {
+        return Ok(false);
+    }
+
+    let mut robots = String::new();
+    for rule in &config.robots.rules {
+        robots.push_str(&format!("User-agent: {}\n", rule.user_agent));
+        for path in &rule.allow {
+            robots.push_str(&format!("Allow: {path}\n"));
+        }
+        for path in &rule.disallow {
+            robots.push_str(&format!("Disallow: {path}\n"));
+        }
+        robots.push('\n');
+    }
+
+    if config.sitemap.enabled {
+        if let Some(sitemap_url) = config.absolute_url("/sitemap.xml") {
+            robots.push_str(&format!("Sitemap: {sitemap_url}\n"));
+        }
+    }
+
+    fs::create_dir_all(&config.public_dir)
+        .with_context(|| format!("Failed to create public directory: {}", config.public_dir.display()))?;
+    let path = config.public_dir.join("robots.txt");
+    fs::write(&path, robots).with_context(|| format!("Failed to write robots.txt: {}", path.display()))?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Post;
+
+    /// Route paths declared in the React app
+    fn app_routes() -> Vec<String> {
+        let app = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../src/App.tsx")).unwrap();
+        regex_lite::Regex::new(r#"<Route\s+path="([^"]+)""#)
+            .unwrap()
+            .captures_iter(&app)
+            .map(|caps| caps[1].to_string())
+            .collect()
+    }
+
+    /// Whether a React Router path such as `/blog/:slug` or `/blog/*` matches a URL path
+    fn route_matches(route: &str, path: &str) -> bool {
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+        for part in route.split('/').filter(|s| !s.is_empty()) {
+            if part == "*" {
+                return segments.next().is_some();
+            }
+            match segments.next() {
+                Some(segment) if part.starts_with(':') || part == segment => {}
+                _ => return false,
+            }
+        }
+        segments.next().is_none()
+    }
+
+    #[test]
+    fn test_sitemap_urls_are_app_routes() {
+        let config = Config::load(concat!(env!("CARGO_MANIFEST_DIR"), "/config.yaml")).unwrap();
+        let routes = app_routes();
+        let served = |path: &str| routes.iter().any(|route| route_matches(route, path));
+
+        for route in &config.sitemap.static_routes {
+            assert!(served(route), "static route {route} is not served by the app");
+        }
+        let collections: Vec<String> = if config.sitemap.collections.is_empty() {
+            config.collections().into_iter().map(|c| c.name).collect()
+        } else {
+            config.sitemap.collections.clone()
+        };
+        for collection in collections {
+            for slug in ["intro", "guides/setup"] {
+                let path = post_path(&collection, slug);
+                assert!(served(&path), "post URL {path} is not served by the app");
+            }
+        }
+        assert!(!served("/about"));
+    }
+
+    #[test]
+    fn test_translations_get_hreflang_alternates() {
+        let dir = std::env::temp_dir().join(format!("site-builder-sitemap-{}", std::process::id()));
+        let mut config = Config {
+            public_dir: dir.clone(),
+            ..Default::default()
+        };
+        config.site.url = Some("https://example.com".to_string());
+        let post = |slug: &str, language: Option<&str>| Post {
+            slug: slug.to_string(),
+            date: "2024-03-20T12:00:00Z".parse().unwrap(),
+            language: language.map(str::to_string),
+            translation_key: Some("intro".to_string()),
+            ..Default::default()
+        };
+        let hidden = Post {
+            seo: Some(crate::seo::build_seo(
+                &config,
+                &crate::seo::SeoInput {
+                    path: "/blog/drafts",
+                    title: "Drafts",
+                    description: None,
+                    image: None,
+                    date: chrono::Utc::now(),
+                    updated: None,
+                    language: None,
+                    tags: &[],
+                },
+                crate::seo::SeoOverrides {
+                    noindex: true,
+                    ..Default::default()
+                },
+            )),
+            ..post("drafts", None)
+        };
+        let builds = [CollectionBuild {
+            collection: config.collections().remove(0),
+            posts: vec![post("intro", None), post("no/intro", Some("nb")), hidden],
+            processed: 3,
+            skipped: 0,
+            errors: Vec::new(),
+        }];
+
+        assert!(write_sitemap(&config, &builds).unwrap());
+        let xml = fs::read_to_string(dir.join("sitemap.xml")).unwrap();
+        assert!(xml.contains("<loc>https://example.com/</loc>"));
+        assert!(xml.contains("<loc>https://example.com/blog/no/intro</loc>\n    <lastmod>2024-03-20</lastmod>"));
+        assert_eq!(xml.matches(r#"hreflang="nb" href="https://example.com/blog/no/intro""#).count(), 2);
+        assert_eq!(xml.matches(r#"hreflang="x-default" href="https://example.com/blog/intro""#).count(), 2);
+        assert!(!xml.contains("drafts"), "noindex posts are not listed");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
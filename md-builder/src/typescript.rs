@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use ts_rs::TS;
+
+use crate::pagination::{ArchiveChunk, IndexManifest, IndexPage, MonthArchive, PageLink, YearArchive};
+use crate::search::{SearchDoc, SearchIndex};
+use crate::sections::{SectionIndex, SectionSummary};
+use crate::cite::{CitationFiles, PostCitation, ReferenceFiles};
+use crate::images::{ImagePlaceholder, ImageSize, ImageSource, ImageVariant};
+use crate::seo::{ArticleMetadata, OpenGraph, SeoMetadata, TwitterCard};
+use crate::{CoverImage, Post, PostIndex, PostSummary};
+
+const HEADER: &str = "// Generated by `site-builder types` from the Rust output structs. Do not edit.\n// Run `cargo run -- types` in md-builder/ after changing them.\n";
+
+/// TypeScript declarations for every JSON file the builder writes
+pub fn render() -> String {
+    let declarations = [
+        Post::decl(),
+        CoverImage::decl(),
+        ImageSize::decl(),
+        ImageVariant::decl(),
+        ImageSource::decl(),
+        ImagePlaceholder::decl(),
+        PostSummary::decl(),
+        PostIndex::decl(),
+        SectionSummary::decl(),
+        SectionIndex::decl(),
+        SeoMetadata::decl(),
+        OpenGraph::decl(),
+        TwitterCard::decl(),
+        ArticleMetadata::decl(),
+        PostCitation::decl(),
+        CitationFiles::decl(),
+        ReferenceFiles::decl(),
+        IndexManifest::decl(),
+        PageLink::decl(),
+        YearArchive::decl(),
+        MonthArchive::decl(),
+        IndexPage::decl(),
+        ArchiveChunk::decl(),
+        SearchIndex::decl(),
+        SearchDoc::decl(),
+        serde_json::Value::decl(),
+    ];
+
+    let mut output = HEADER.to_string();
+    for declaration in declarations {
+        output.push('\n');
+        output.push_str("export ");
+        output.push_str(&format_declaration(&declaration));
+        output.push('\n');
+    }
+    output
+}
+
+/// Write the declarations to `path`, or with `check` only compare them.
+/// Returns whether the file on disk was up to date.
+pub fn write_types(path: &Path, check: bool) -> Result<bool> {
+    let generated = render();
+    let current = fs::read_to_string(path).unwrap_or_default();
+    if current == generated {
+        return Ok(true);
+    }
+    if !check {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create types directory: {}", parent.display()))?;
+        }
+        fs::write(path, generated)
+            .with_context(|| format!("Failed to write TypeScript types: {}", path.display()))?;
+    }
+    Ok(false)
+}
+
+/// Put each member of a `type X = { a: A, b: B, };` object type on its own line
+fn format_declaration(declaration: &str) -> String {
+    let Some(body_start) = declaration.find("= {").map(|i| i + 3) else {
+        return declaration.to_string();
+    };
+
+    let mut output = declaration[..body_start].to_string();
+    let mut member = String::new();
+    let mut depth = 0usize;
+    let mut in_comment = false;
+    let mut rest = declaration[body_start..].chars();
+
+    for c in rest.by_ref() {
+        member.push(c);
+        if in_comment {
+            in_comment = !member.ends_with("*/");
+            continue;
+        }
+        match c {
+            '*' if member.ends_with("/*") => in_comment = true,
+            '{' | '(' | '[' | '<' => depth += 1,
+            '}' | ')' | ']' | '>' if depth > 0 => depth -= 1,
+            '}' => {
+                member.pop();
+                break;
+            }
+            ',' if depth == 0 => {
+                member.pop();
+                push_member(&mut output, &member);
+                member.clear();
+            }
+            _ => {}
+        }
+    }
+    push_member(&mut output, &member);
+    output.push_str("\n}");
+    output.extend(rest);
+    output
+}
+
+fn push_member(output: &mut String, member: &str) {
+    let member = member.trim();
+    if member.is_empty() {
+        return;
+    }
+    // Doc comments are emitted as `/** ... */` directly in front of the member
+    let (doc, member) = match member.rfind("*/") {
+        Some(end) => member.split_at(end + 2),
+        None => ("", member),
+    };
+    for line in doc.lines() {
+        output.push_str(&format!("\n  {}", if line.starts_with(' ') { line } else { line.trim() }));
+    }
+    output.push_str(&format!("\n  {},", member.trim()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_committed_types_are_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/types/blog.ts");
+        assert!(
+            write_types(&path, true).unwrap(),
+            "src/types/blog.ts is stale, run `cargo run -- types` in md-builder/"
+        );
+    }
+}
@@ -256,7 +256,7 @@ mod tests {
             ("ZOTERO_USER_ID").expect
             ("ZOTERO_USER_ID must be set");
 
-        let zotero = ZoteroClient::new(api_key, user_id
+        let zotero = ZoteroClient::new(api_key.clone(), user_id
             .to_string()).unwrap();
 
         let endpoint = format!("{ZOTERO_API_BASE}users/{user_id}/items");
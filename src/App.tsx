@@ -18,7 +18,8 @@ export default function App() {
           <Routes>
             <Route path="/" element={<LandingPage />} />
             <Route path="/blog" element={<BlogIndex />} />
-            <Route path="/blog/:slug" element={<BlogPost />} />
+            {/* Slugs of posts in sections have several segments, e.g. /blog/guides/setup */}
+            <Route path="/blog/*" element={<BlogPost />} />
           </Routes>
         </div>
       </ThemeProvider>
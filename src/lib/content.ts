@@ -1,14 +1,33 @@
-import { Post } from "@/types/blog"
+import { IndexManifest, IndexPage, Post } from "@/types/blog"
 
-// Import all JSON files from assets - adjust the path according to your build output
-const postFiles = import.meta.glob<Post>('/src/assets/content/*.json', { eager: true })
+// Post JSON written by md-builder, nested sections included. Each post is its own chunk,
+// loaded when it is opened. Index files and the citation library live next to the posts,
+// so they are excluded.
+const postFiles = import.meta.glob<Post>(
+  ['/src/assets/content/**/*.json', '!/src/assets/content/**/index.json', '!/src/assets/content/citations.json'],
+  { import: 'default' }
+)
 
-// Convert the imported object into an array and sort by date
-export const allPosts = Object.entries(postFiles).map(([path, post]) => ({
-  ...post,
-  // Extract slug from filename (e.g., /src/assets/first-post.json -> first-post)
-  slug: path.split('/').pop()?.replace('.json', '') ?? ''
-})).sort((a, b) => new Date(b.date).getTime() - new Date(a.date).getTime())
+// The post with a slug like `guides/setup`, or undefined when there is none
+export async function loadPost(slug: string): Promise<Post | undefined> {
+  const load = postFiles[`/src/assets/content/${slug}.json`]
+  return load ? load() : undefined
+}
 
-// Debugging - remove this in production
-console.log('Found posts:', allPosts)
\ No newline at end of file
+// Manifest of a collection's index pages, written to `public/<collection>/index/`
+export function fetchIndexManifest(collection: string): Promise<IndexManifest> {
+  return fetchJson(`/${collection}/index/manifest.json`)
+}
+
+// One page of summaries, from a URL in the manifest or a page's `next` link
+export function fetchIndexPage(url: string): Promise<IndexPage> {
+  return fetchJson(url)
+}
+
+async function fetchJson<T>(url: string): Promise<T> {
+  const response = await fetch(url)
+  if (!response.ok) {
+    throw new Error(`Failed to load ${url}: ${response.status}`)
+  }
+  return response.json() as Promise<T>
+}
@@ -3,6 +3,7 @@ import { createRoot } from 'react-dom/client'
 import './index.css'
 import App from './App.tsx'
 
+// Prerendered pages ship template markup in #root for crawlers; React replaces it
 createRoot(document.getElementById('root')!).render(
   <StrictMode>
     <App />
@@ -1,4 +1,4 @@
-import { useState, useMemo } from 'react';
+import { useState, useMemo, useEffect } from 'react';
 
 import { Container } from '@/components/ui/container';
 import { Section } from '@/components/ui/section';
@@ -6,22 +6,58 @@ import { PostCard } from '@/components/ui/post-card';
 import { FilterButton } from '@/components/ui/filter-button';
 import { Input } from '@/components/ui/input';
 import { Search } from 'lucide-react';
-import allPosts from '@/assets/content/index.json';
+import { fetchIndexManifest, fetchIndexPage } from '@/lib/content';
+import { PostSummary } from '@/types/blog';
 
 export default function BlogIndex() {
   const [searchQuery, setSearchQuery] = useState('');
   const [selectedTag, setSelectedTag] = useState<string | null>(null);
+  // Summaries of the index pages loaded so far, and the URL of the next one
+  const [posts, setPosts] = useState<PostSummary[]>([]);
+  const [nextPage, setNextPage] = useState<string>();
+  const [loading, setLoading] = useState(true);
 
-  // Get all unique tags
+  useEffect(() => {
+    let current = true;
+    fetchIndexManifest('blog')
+      .then(manifest => fetchIndexPage(manifest.pages[0].url))
+      .then(page => {
+        if (current) {
+          setPosts(page.posts);
+          setNextPage(page.next);
+        }
+      })
+      .catch(console.error)
+      .finally(() => {
+        if (current) setLoading(false);
+      });
+    return () => {
+      current = false;
+    };
+  }, []);
+
+  const loadMore = () => {
+    if (!nextPage) return;
+    setLoading(true);
+    fetchIndexPage(nextPage)
+      .then(page => {
+        setPosts(loaded => [...loaded, ...page.posts]);
+        setNextPage(page.next);
+      })
+      .catch(console.error)
+      .finally(() => setLoading(false));
+  };
+
+  // Get all unique tags of the loaded posts
   const allTags = useMemo(() => {
     const tags = new Set<string>();
-    allPosts.posts.forEach(post => post.tags.forEach(tag => tags.add(tag)));
+    posts.forEach(post => post.tags.forEach(tag => tags.add(tag)));
     return Array.from(tags);
-  }, []);
+  }, [posts]);
 
   // Filter posts based on search and tags
   const filteredPosts = useMemo(() => {
-    return allPosts.posts.filter(post => {
+    return posts.filter(post => {
       const matchesSearch = searchQuery === '' || 
         post.title.toLowerCase().includes(searchQuery.toLowerCase()) ||
         post.description?.toLowerCase().includes(searchQuery.toLowerCase());
@@ -30,7 +66,7 @@ export default function BlogIndex() {
       
       return matchesSearch && matchesTag;
     });
-  }, [searchQuery, selectedTag]);
+  }, [posts, searchQuery, selectedTag]);
 
   return (
     <Section>
@@ -49,14 +85,23 @@ export default function BlogIndex() {
                   month: 'long',
                   day: 'numeric'
                 })}
-                description={post.description}
+                description={post.description ?? undefined}
                 tags={post.tags}
                 imageUrl={post.cover?.thumbnail || undefined}
               />
             ))}
 
+            {/* More pages of the index */}
+            {nextPage && (
+              <div className="text-center">
+                <FilterButton onClick={loadMore} disabled={loading}>
+                  Load more posts
+                </FilterButton>
+              </div>
+            )}
+
             {/* Empty State */}
-            {filteredPosts.length === 0 && (
+            {!loading && filteredPosts.length === 0 && (
               <div className="text-center py-12">
                 <p className="text-light-secondary dark:text-dark-secondary text-lg mb-4">
                   No posts found matching your criteria
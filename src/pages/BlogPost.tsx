@@ -1,3 +1,4 @@
+import { useEffect, useState } from 'react';
 import { useParams } from 'react-router-dom';
 import { ChevronLeft, Clock, Calendar, ChevronRight } from 'lucide-react';
 import { Container } from '@/components/ui/container';
@@ -6,12 +7,29 @@ import { LinkButton } from '@/components/ui/button';
 import { Tag } from '@/components/ui/tag';
 import { Article } from '@/components/ui/article';
 import { Prose } from '@/components/ui/prose';
-import { allPosts } from '@/lib/content';
+import { loadPost } from '@/lib/content';
+import { Post } from '@/types/blog';
 import { calculateReadTime } from '@/lib/utils';
 
 export default function BlogPost() {
-  const { slug } = useParams<{ slug: string }>();
-  const post = allPosts.find(p => p.slug === slug);
+  const { '*': slug } = useParams();
+  // Undefined while the post loads, null when there is none with this slug
+  const [post, setPost] = useState<Post | null>();
+
+  useEffect(() => {
+    let current = true;
+    setPost(undefined);
+    loadPost(slug ?? '').then(loaded => {
+      if (current) setPost(loaded ?? null);
+    });
+    return () => {
+      current = false;
+    };
+  }, [slug]);
+
+  if (post === undefined) {
+    return <div className="min-h-screen bg-light dark:bg-dark" />;
+  }
 
   if (!post) {
     return (
@@ -35,7 +53,22 @@ export default function BlogPost() {
     );
   }
   const imageUrl = post.cover?.cover;
-  const thumbnailUrl = post.cover?.thumbnail;
+  // Intrinsic size of the cover, so the browser reserves its space before it loads
+  const imageSize = post.cover?.variants.find((variant) => variant.url === imageUrl);
+  // One <source> per extra format, e.g. AVIF and WebP, with every width of it
+  const sourceSets = Object.entries(
+    (post.cover?.sources ?? []).reduce<Record<string, string[]>>((sets, source) => {
+      (sets[source.mime_type] ??= []).push(`${source.url} ${source.width}w`);
+      return sets;
+    }, {})
+  );
+  // Dominant color and blurred LQIP behind the cover until it has loaded
+  const placeholder = post.cover?.placeholder;
+  const placeholderStyle = placeholder && {
+    backgroundColor: placeholder.color,
+    backgroundImage: `url(${placeholder.lqip})`,
+    backgroundSize: 'cover',
+  };
   const readTime = calculateReadTime(post.content);
 
   return (
@@ -86,15 +119,30 @@ export default function BlogPost() {
 
             {/* Cover Image */}
             {imageUrl && (
-              <div className="mb-12 rounded-xl overflow-hidden bg-light-surface dark:bg-dark-surface border border-light dark:border-dark">
-                <img
-                  src={imageUrl}
-                  alt={post.title}
-                  loading="lazy"
-                  srcSet={`${thumbnailUrl} 400w, ${imageUrl} 1200w`}
-                  sizes="(max-width: 768px) 100vw, 1200px"
-                  className="w-full h-auto aspect-[2/1] object-cover"
-                />
+              <div
+                className="mb-12 rounded-xl overflow-hidden bg-light-surface dark:bg-dark-surface border border-light dark:border-dark"
+                style={placeholderStyle || undefined}
+              >
+                <picture>
+                  {sourceSets.map(([type, srcSet]) => (
+                    <source
+                      key={type}
+                      type={type}
+                      srcSet={srcSet.join(', ')}
+                      sizes="(max-width: 768px) 100vw, 1200px"
+                    />
+                  ))}
+                  <img
+                    src={imageUrl}
+                    alt={post.title}
+                    loading="lazy"
+                    srcSet={post.cover?.srcset}
+                    width={imageSize?.width}
+                    height={imageSize?.height}
+                    sizes="(max-width: 768px) 100vw, 1200px"
+                    className="w-full h-auto aspect-[2/1] object-cover"
+                  />
+                </picture>
               </div>
             )}
 
@@ -1,27 +1,310 @@
-export interface Post {
-    title: string;
-    date: string;
-    slug: string;
-    content: string;
-    description?: string;
-    tags: string[];
-    cover?: PostCover;
-  }
-
-  export interface PostCover {
-    original: string;
-    cover: string;
-    thumbnail: string;
-  }
-  
-  export interface PostSummary {
-    title: string;
-    date: string;
-    slug: string;
-    description?: string;
-    tags: string[];
-  }
-  
-  export interface PostIndex {
-    posts: PostSummary[];
-  }
\ No newline at end of file
+// Generated by `site-builder types` from the Rust output structs. Do not edit.
+// Run `cargo run -- types` in md-builder/ after changing them.
+
+export type Post = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  title: string,
+  date: string,
+  updated?: string,
+  slug: string,
+  section?: string,
+  language?: string,
+  /**
+   * Shared by all language versions of the same post
+   */
+  translation_key?: string,
+  content: string,
+  description?: string,
+  /**
+   * Always present, empty for posts without tags
+   */
+  tags: Array<string>,
+  /**
+   * Name of the series the post belongs to, e.g. for EPUB exports
+   */
+  series?: string,
+  cover?: CoverImage,
+  /**
+   * Canonical URL, Open Graph and Twitter card metadata
+   */
+  seo?: SeoMetadata,
+  /**
+   * Schema.org `BlogPosting` or `ScholarlyArticle` data for a JSON-LD script
+   */
+  json_ld?: JsonValue,
+  /**
+   * BibTeX, RIS and CSL-JSON records for citing the post
+   */
+  cite?: PostCitation,
+} & ({ [key in string]?: number | string | boolean | Array<JsonValue> | { [key in string]?: JsonValue } | null });
+
+export type CoverImage = {
+  original: string,
+  original_size: ImageSize,
+  cover: string,
+  thumbnail: string,
+  /**
+   * Every preset in the source format, narrowest first, for the `<img>` `srcset`
+   */
+  srcset: string,
+  /**
+   * Every preset in the source format with its pixel size, narrowest first
+   */
+  variants: Array<ImageVariant>,
+  /**
+   * Every preset in the configured extra formats, for `<picture>` sources
+   */
+  sources?: Array<ImageSource>,
+  /**
+   * BlurHash, LQIP and dominant color to show until the cover has loaded
+   */
+  placeholder?: ImagePlaceholder,
+};
+
+export type ImageSize = {
+  width: number,
+  height: number,
+  /**
+   * `width / height`, rounded to four decimals
+   */
+  aspect_ratio: number,
+};
+
+export type ImageVariant = {
+  /**
+   * Name of the preset
+   */
+  name: string,
+  url: string,
+  width: number,
+  height: number,
+  /**
+   * `width / height`, rounded to four decimals
+   */
+  aspect_ratio: number,
+};
+
+export type ImageSource = {
+  /**
+   * `avif` or `webp`
+   */
+  format: string,
+  /**
+   * Value for the `type` attribute, e.g. `image/avif`
+   */
+  mime_type: string,
+  url: string,
+  width: number,
+  height: number,
+  /**
+   * `width / height`, rounded to four decimals
+   */
+  aspect_ratio: number,
+};
+
+export type ImagePlaceholder = {
+  /**
+   * BlurHash of the cover, see https://blurha.sh
+   */
+  blurhash: string,
+  /**
+   * A few pixels wide PNG of the cover as a `data:` URL, to be scaled up and blurred
+   */
+  lqip: string,
+  /**
+   * Most common color of the cover as `#rrggbb`
+   */
+  color: string,
+};
+
+export type PostSummary = {
+  title: string,
+  date: string,
+  slug: string,
+  section?: string,
+  language?: string,
+  description: string | null,
+  tags: Array<string>,
+  cover: CoverImage | null,
+} & ({ [key in string]?: number | string | boolean | Array<JsonValue> | { [key in string]?: JsonValue } | null });
+
+export type PostIndex = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  title?: string,
+  description?: string,
+  sections?: Array<SectionSummary>,
+  posts: Array<PostSummary>,
+};
+
+export type SectionSummary = {
+  path: string,
+  title: string,
+  description?: string,
+  post_count: number,
+};
+
+export type SectionIndex = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  path: string,
+  title: string,
+  description?: string,
+  content?: string,
+  subsections?: Array<SectionSummary>,
+  posts: Array<PostSummary>,
+};
+
+export type SeoMetadata = {
+  title: string,
+  description?: string,
+  canonical_url?: string,
+  noindex?: boolean,
+  open_graph: OpenGraph,
+  twitter: TwitterCard,
+  article: ArticleMetadata,
+};
+
+export type OpenGraph = {
+  type: string,
+  title: string,
+  description?: string,
+  url?: string,
+  image?: string,
+  site_name: string,
+  locale: string,
+};
+
+export type TwitterCard = {
+  card: string,
+  title: string,
+  description?: string,
+  image?: string,
+};
+
+export type ArticleMetadata = {
+  published_time: string,
+  modified_time?: string,
+  author?: string,
+  tags?: Array<string>,
+};
+
+export type PostCitation = {
+  /**
+   * The post as a reference in the configured citation style, as plain text
+   */
+  formatted?: string,
+  bibtex: string,
+  ris: string,
+  csl_json: JsonValue,
+  /**
+   * Site paths of the same records as downloadable files
+   */
+  files: CitationFiles,
+  /**
+   * Downloads of the works the post cites, when it cites any
+   */
+  references?: ReferenceFiles,
+};
+
+export type CitationFiles = {
+  bibtex: string,
+  ris: string,
+  csl_json: string,
+};
+
+export type ReferenceFiles = {
+  bibtex: string,
+  ris: string,
+  count: number,
+};
+
+export type IndexManifest = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  total_posts: number,
+  page_size: number,
+  total_pages: number,
+  pages: Array<PageLink>,
+  archives?: Array<YearArchive>,
+};
+
+export type PageLink = {
+  page: number,
+  url: string,
+  count: number,
+};
+
+export type YearArchive = {
+  year: number,
+  url: string,
+  count: number,
+  months: Array<MonthArchive>,
+};
+
+export type MonthArchive = {
+  month: number,
+  url: string,
+  count: number,
+};
+
+export type IndexPage = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  page: number,
+  total_pages: number,
+  prev?: string,
+  next?: string,
+  posts: Array<PostSummary>,
+};
+
+export type ArchiveChunk = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  year: number,
+  month?: number,
+  posts: Array<PostSummary>,
+};
+
+export type SearchIndex = {
+  /**
+   * Output format version, see `schema::SCHEMA_VERSION`
+   */
+  schema_version: number,
+  version: number,
+  language: string,
+  fields: [string, string, string, string],
+  docs: Array<SearchDoc>,
+  /**
+   * Stemmed term to postings. Each posting is `[doc, field, count, snippet, snippet, ...]`:
+   * how often the term occurs in the field and which of the doc's snippets contain it.
+   */
+  terms: { [key in string]?: Array<Array<number>> },
+};
+
+export type SearchDoc = {
+  url: string,
+  title: string,
+  description?: string,
+  tags: Array<string>,
+  /**
+   * The first distinct sentences of the headings and body, cut to 160 characters,
+   * to show as highlighted excerpts
+   */
+  snippets: Array<string>,
+};
+
+export type JsonValue = number | string | boolean | Array<JsonValue> | { [key in string]?: JsonValue } | null;
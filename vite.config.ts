@@ -18,6 +18,8 @@ export default defineConfig({
     }
   },
   build: {
+    // Lets the prerendered pages load the hashed entry script and CSS
+    manifest: true,
     // Copy content files to build output
     rollupOptions: {
       input: {